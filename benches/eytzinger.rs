@@ -1,12 +1,13 @@
 use criterion::{Criterion, criterion_group, criterion_main};
 use isld::EytzingerTree;
+use isld::stree::STree;
 use std::hint::black_box;
 fn benchmark_eytzinger(c: &mut Criterion) {
     let sizes = vec![100usize, 1_000, 10_000, 100_000, 1_000_000];
 
     for size in sizes {
         let data: Vec<usize> = (0..size).collect();
-        let eytz = EytzingerTree::new(&data);
+        let eytz = EytzingerTree::new(data.clone());
 
         let mut group = c.benchmark_group(format!("search_size_{}", size));
 
@@ -21,7 +22,7 @@ fn benchmark_eytzinger(c: &mut Criterion) {
         group.bench_function("eytzinger_simple", |b| {
             b.iter(|| {
                 for i in (0..size).step_by(size / 100) {
-                    black_box(eytz.search(i));
+                    black_box(eytz.search(&i));
                 }
             });
         });
@@ -29,7 +30,7 @@ fn benchmark_eytzinger(c: &mut Criterion) {
         group.bench_function("eytzinger_branchless", |b| {
             b.iter(|| {
                 for i in (0..size).step_by(size / 100) {
-                    black_box(eytz.search_optimized(i));
+                    black_box(eytz.search_optimized(&i));
                 }
             });
         });
@@ -37,14 +38,95 @@ fn benchmark_eytzinger(c: &mut Criterion) {
         group.bench_function("eytzinger_prefetch", |b| {
             b.iter(|| {
                 for i in (0..size).step_by(size / 100) {
-                    black_box(eytz.search_prefetch(i));
+                    black_box(eytz.search_prefetch(&i));
                 }
             });
         });
 
+        let keys: Vec<usize> = (0..size).step_by(size / 100).collect();
+        let mut out = vec![None; keys.len()];
+        group.bench_function("eytzinger_batch", |b| {
+            b.iter(|| {
+                eytz.search_batch(black_box(&keys), &mut out);
+                black_box(&out);
+            });
+        });
+
+        group.bench_function("std_partition_point", |b| {
+            b.iter(|| {
+                for i in (0..size).step_by(size / 100) {
+                    black_box(data.partition_point(|&x| x < i));
+                }
+            });
+        });
+
+        group.bench_function("eytzinger_lower_bound", |b| {
+            b.iter(|| {
+                for i in (0..size).step_by(size / 100) {
+                    black_box(eytz.lower_bound(&i));
+                }
+            });
+        });
+
+        group.finish();
+    }
+}
+
+fn benchmark_stree(c: &mut Criterion) {
+    let sizes = vec![100usize, 1_000, 10_000, 100_000, 1_000_000];
+
+    for size in sizes {
+        let data: Vec<u32> = (0..size as u32).collect();
+        let eytz = EytzingerTree::new(data.clone());
+        let stree = STree::new(&data);
+
+        let keys: Vec<u32> = (0..size as u32).step_by(size / 100).collect();
+        let mut ranks = vec![0; keys.len()];
+        let mut found = vec![None; keys.len()];
+
+        let mut group = c.benchmark_group(format!("lower_bound_size_{}", size));
+
+        group.bench_function("std_partition_point", |b| {
+            b.iter(|| {
+                for &i in &keys {
+                    black_box(data.partition_point(|&x| x < i));
+                }
+            });
+        });
+
+        group.bench_function("eytzinger_lower_bound", |b| {
+            b.iter(|| {
+                for &i in &keys {
+                    black_box(eytz.lower_bound(&i));
+                }
+            });
+        });
+
+        group.bench_function("eytzinger_batch", |b| {
+            b.iter(|| {
+                eytz.search_batch(black_box(&keys), &mut found);
+                black_box(&found);
+            });
+        });
+
+        group.bench_function("stree_lower_bound", |b| {
+            b.iter(|| {
+                for &i in &keys {
+                    black_box(stree.lower_bound(i));
+                }
+            });
+        });
+
+        group.bench_function("stree_batch", |b| {
+            b.iter(|| {
+                stree.lower_bound_batch(black_box(&keys), &mut ranks);
+                black_box(&ranks);
+            });
+        });
+
         group.finish();
     }
 }
 
-criterion_group!(benches, benchmark_eytzinger);
+criterion_group!(benches, benchmark_eytzinger, benchmark_stree);
 criterion_main!(benches);
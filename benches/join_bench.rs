@@ -30,8 +30,6 @@ struct JoinWorkload {
     build_tuples: Vec<(u32, u64)>,
     /// Keys to probe (mix of matching and non-matching)
     probe_keys: Vec<u32>,
-    /// Human-readable label
-    label: String,
 }
 
 impl JoinWorkload {
@@ -79,14 +77,9 @@ impl JoinWorkload {
         // Shuffle probe order
         probe_keys.shuffle(&mut rng);
 
-        let total_build = build_keys * multiplicity;
-        let label =
-            format!("build={total_build}/probe={probe_count}/sel={selectivity}/mul={multiplicity}");
-
         Self {
             build_tuples,
             probe_keys,
-            label,
         }
     }
 }
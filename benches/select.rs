@@ -0,0 +1,110 @@
+//! Selection benchmark: `slice::select_nth_unstable` vs QuickSelect vs Floyd–Rivest
+//! vs parallel selection, and the block partition kernel vs three-way partitioning
+//! on random, sorted and duplicate-heavy inputs.
+//!
+//! Each iteration selects a percentile from a fresh copy of a shuffled array,
+//! the way percentile computations run over large buffers.
+
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+use isld::select::bench::{partition, partition_three_way};
+use isld::select::{Strategy, par_select_nth, select_nth_with};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::hint::black_box;
+
+fn benchmark_select(c: &mut Criterion) {
+    let mut rng = ChaCha8Rng::seed_from_u64(42);
+
+    for size in [100_000usize, 1_000_000, 10_000_000] {
+        let data: Vec<u64> = (0..size).map(|_| rng.random()).collect();
+        let mut group = c.benchmark_group(format!("select_size_{}", size));
+        group.sample_size(20);
+
+        for (label, k) in [("p50", size / 2), ("p99", size / 100 * 99)] {
+            group.bench_with_input(
+                BenchmarkId::new("std_select_nth_unstable", label),
+                &k,
+                |b, &k| {
+                    b.iter_batched_ref(
+                        || data.clone(),
+                        |work| {
+                            black_box(work.select_nth_unstable(k));
+                        },
+                        BatchSize::LargeInput,
+                    );
+                },
+            );
+
+            for (name, strategy) in [
+                ("quickselect", Strategy::QuickSelect),
+                ("floyd_rivest", Strategy::FloydRivest),
+            ] {
+                group.bench_with_input(BenchmarkId::new(name, label), &k, |b, &k| {
+                    b.iter_batched_ref(
+                        || data.clone(),
+                        |work| {
+                            black_box(select_nth_with(work, k, strategy));
+                        },
+                        BatchSize::LargeInput,
+                    );
+                });
+            }
+
+            group.bench_with_input(BenchmarkId::new("parallel", label), &k, |b, &k| {
+                b.iter_batched_ref(
+                    || data.clone(),
+                    |work| {
+                        black_box(par_select_nth(work, k));
+                    },
+                    BatchSize::LargeInput,
+                );
+            });
+        }
+
+        group.finish();
+    }
+}
+
+fn benchmark_partition(c: &mut Criterion) {
+    let mut rng = ChaCha8Rng::seed_from_u64(7);
+    let size = 1_000_000usize;
+
+    let inputs: [(&str, Vec<u64>); 3] = [
+        ("random", (0..size).map(|_| rng.random()).collect()),
+        ("sorted", (0..size as u64).collect()),
+        (
+            "duplicates",
+            (0..size).map(|_| rng.random_range(0..16)).collect(),
+        ),
+    ];
+
+    let mut group = c.benchmark_group(format!("partition_size_{}", size));
+    group.sample_size(20);
+
+    for (label, data) in &inputs {
+        group.bench_with_input(BenchmarkId::new("block", label), data, |b, data| {
+            b.iter_batched_ref(
+                || data.clone(),
+                |work| {
+                    black_box(partition(work, size / 2));
+                },
+                BatchSize::LargeInput,
+            );
+        });
+
+        group.bench_with_input(BenchmarkId::new("three_way", label), data, |b, data| {
+            b.iter_batched_ref(
+                || data.clone(),
+                |work| {
+                    black_box(partition_three_way(work, size / 2));
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_select, benchmark_partition);
+criterion_main!(benches);
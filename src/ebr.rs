@@ -5,6 +5,18 @@
 //! *retire* pointers they remove. Retired pointers are only freed once every
 //! thread has moved past the epoch in which the pointer was retired.
 //!
+//! No operation takes a lock. Threads are tracked by an intrusive lock-free
+//! list of participants, and retired pointers first go into a bag local to
+//! the retiring thread. A full bag is sealed with the current epoch and pushed
+//! onto a global lock-free list, where any thread can free it once the epoch
+//! has moved far enough past the seal.
+//!
+//! ```text
+//!  participants  [ epoch | active ] -> [ epoch | active ] -> ...   never unlinked, reused
+//!  local bag     [ (e, ptr) (e, ptr) ... ]                         up to BAG_CAP entries
+//!  global bags   [ sealed e=7 ] -> [ sealed e=5 ] -> ...           Treiber stack
+//! ```
+//!
 //! # Usage
 //!
 //! ```ignore
@@ -19,23 +31,127 @@
 //! guard.defer_destroy(retired_ptr);
 //! // guard unpins on drop, may trigger GC.
 //! ```
+//!
+//! Shared pointers can be kept in an [`Atomic`] instead of an `AtomicPtr`.
+//! Its loads return a [`Shared`] tied to the guard, and a failed
+//! `compare_exchange` hands an [`Owned`] allocation back for the next retry:
+//!
+//! ```ignore
+//! let head = Atomic::new(Node { value: 1, next: Atomic::null() });
+//!
+//! let guard = handle.pin();
+//! let old = head.load(Ordering::Acquire, &guard);
+//! match head.compare_exchange(old, Owned::new(node), Ordering::AcqRel, Ordering::Acquire, &guard) {
+//!     Ok(_) => guard.defer_destroy(old.as_raw()),
+//!     Err(e) => retry_with(e.new),
+//! }
+//! ```
+//!
+//! Reclamation normally happens opportunistically as guards drop. To free
+//! everything retired so far at a known point, flush the local bag and wait
+//! for a grace period:
+//!
+//! ```ignore
+//! handle.pin().flush();
+//! collector.synchronize();
+//! ```
 
-use std::sync::{
-    Arc, Mutex,
-    atomic::{AtomicUsize, Ordering},
+use std::{
+    cell::{Cell, RefCell},
+    marker::PhantomData,
+    mem::MaybeUninit,
+    ptr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering, fence},
+    },
 };
 
-/// Type-erased record of a pointer waiting to be freed.
+/// Epoch value of a participant that is not pinned.
+const INACTIVE: usize = usize::MAX;
+
+/// Garbage entries a local bag holds before it is sealed and pushed to the
+/// global list.
+const BAG_CAP: usize = 64;
+
+/// Words of inline closure storage in a [`Garbage`] entry.
+const INLINE_WORDS: usize = 3;
+
+type InlineData = MaybeUninit<[usize; INLINE_WORDS]>;
+
+/// Type-erased deferred function waiting for its epoch to become safe.
+///
+/// Closures up to [`INLINE_WORDS`] words are stored in place, so retiring a
+/// pointer or a closure capturing a few handles does not allocate. Larger
+/// closures are boxed.
 struct Garbage {
     epoch: usize,
-    ptr: *mut u8,
-    deleter: unsafe fn(*mut u8),
+    call: unsafe fn(*mut u8),
+    data: InlineData,
 }
 
-// SAFETY: The pointer is only accessed via the type-erased deleter which
-// correctly reconstructs the original type.
+// SAFETY: `Guard::defer` only accepts `Send` closures, and callers of
+// `Guard::defer_unchecked` promise that running the closure elsewhere is
+// sound.
 unsafe impl Send for Garbage {}
 
+impl Garbage {
+    /// Wraps `f`, which will be called by [`Garbage::call`].
+    fn new<F: FnOnce()>(epoch: usize, f: F) -> Self {
+        let mut data = InlineData::uninit();
+        if fits_inline::<F>() {
+            unsafe fn call<F: FnOnce()>(raw: *mut u8) {
+                let f: F = unsafe { ptr::read(raw as *mut F) };
+                f();
+            }
+
+            unsafe { ptr::write(data.as_mut_ptr() as *mut F, f) };
+            Self {
+                epoch,
+                call: call::<F>,
+                data,
+            }
+        } else {
+            unsafe fn call<F: FnOnce()>(raw: *mut u8) {
+                let f: Box<F> = unsafe { ptr::read(raw as *mut Box<F>) };
+                (*f)();
+            }
+
+            unsafe { ptr::write(data.as_mut_ptr() as *mut Box<F>, Box::new(f)) };
+            Self {
+                epoch,
+                call: call::<F>,
+                data,
+            }
+        }
+    }
+
+    /// Runs the deferred function.
+    fn call(mut self) {
+        unsafe { (self.call)(self.data.as_mut_ptr() as *mut u8) };
+    }
+}
+
+/// Runs every entry of `garbage`. If one panics, the rest still run while the
+/// panic unwinds instead of leaking, and a second panic aborts.
+fn run_all<I: Iterator<Item = Garbage>>(garbage: I) {
+    struct Drain<I: Iterator<Item = Garbage>>(I);
+
+    impl<I: Iterator<Item = Garbage>> Drop for Drain<I> {
+        fn drop(&mut self) {
+            for g in &mut self.0 {
+                g.call();
+            }
+        }
+    }
+
+    // Dropping the drain runs whatever the loop didn't get to.
+    let mut drain = Drain(garbage);
+    for g in &mut drain.0 {
+        g.call();
+    }
+}
+
 /// Type-erased deleter that reconstructs and drops a `Box<T>`.
 unsafe fn drop_box<T>(ptr: *mut u8) {
     unsafe {
@@ -43,12 +159,48 @@ unsafe fn drop_box<T>(ptr: *mut u8) {
     }
 }
 
-/// Owns all shared EBR state: the global epoch, the thread registry, and the
-/// garbage list. Create one per logical "domain" of shared pointers.
+/// Whether a closure of type `F` is stored inline in a [`Garbage`] entry.
+const fn fits_inline<F>() -> bool {
+    size_of::<F>() <= size_of::<InlineData>() && align_of::<F>() <= align_of::<InlineData>()
+}
+
+/// Whether garbage retired at `epoch` can be freed once the global epoch is
+/// `current`: every thread pinned when it was retired has unpinned since.
+fn is_reclaimable(epoch: usize, current: usize) -> bool {
+    epoch + 3 <= current
+}
+
+/// A full local bag, sealed with the epoch at which it was pushed. Every entry
+/// was retired at or before that epoch.
+struct SealedBag {
+    epoch: usize,
+    garbage: Vec<Garbage>,
+    next: *mut SealedBag,
+}
+
+/// Entry of the participant list. Nodes are never unlinked while the
+/// collector is alive: a handle going away clears `active` and the next
+/// handle to register claims the node back.
+struct Participant {
+    /// Epoch the owning thread is pinned at, or [`INACTIVE`].
+    epoch: AtomicUsize,
+    /// Whether a [`LocalHandle`] currently owns this node.
+    active: AtomicBool,
+    next: AtomicPtr<Participant>,
+}
+
+/// Owns all shared EBR state: the global epoch, the participant list, and the
+/// global garbage list. Create one per logical "domain" of shared pointers.
 pub struct Collector {
     epoch: AtomicUsize,
-    threads: Mutex<Vec<Arc<AtomicUsize>>>,
-    garbage: Mutex<Vec<Garbage>>,
+    participants: AtomicPtr<Participant>,
+    garbage: AtomicPtr<SealedBag>,
+    /// Number of `gc` calls in flight, by the parity of the generation they
+    /// started in.
+    collecting: [AtomicUsize; 2],
+    /// Bumped by `synchronize` to tell the collections already in flight
+    /// apart from the ones that start later.
+    generation: AtomicUsize,
 }
 
 impl Collector {
@@ -57,83 +209,224 @@ impl Collector {
     pub fn new() -> Arc<Self> {
         Arc::new(Self {
             epoch: AtomicUsize::new(0),
-            threads: Mutex::new(Vec::new()),
-            garbage: Mutex::new(Vec::new()),
+            participants: AtomicPtr::new(ptr::null_mut()),
+            garbage: AtomicPtr::new(ptr::null_mut()),
+            collecting: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            generation: AtomicUsize::new(0),
         })
     }
 
     /// Register a thread and obtain a [`LocalHandle`] for pinning.
     pub fn register(self: &Arc<Self>) -> LocalHandle {
-        let epoch = Arc::new(AtomicUsize::new(usize::MAX));
-        self.threads.lock().unwrap().push(epoch.clone());
         LocalHandle {
             collector: Arc::clone(self),
-            epoch,
+            participant: self.acquire_participant(),
+            bag: RefCell::new(Vec::with_capacity(BAG_CAP)),
+            guards: Cell::new(0),
         }
     }
 
-    /// Try to advance the global epoch. Uses `try_lock` to avoid contention —
-    /// if another thread is already checking, we simply skip this attempt.
+    /// Block until every thread pinned at the time of the call has unpinned,
+    /// then free the global garbage retired before the call (RCU-style
+    /// `synchronize`). Every such bag has been freed by the time this returns.
+    ///
+    /// Garbage still sitting in a thread's local bag is only freed once that
+    /// thread calls [`Guard::flush`] or [`LocalHandle::collect`].
+    ///
+    /// Must not be called while the calling thread is pinned, since its own
+    /// guard would keep the epoch from advancing forever.
+    pub fn synchronize(&self) {
+        let target = self.current_epoch() + 3;
+        while self.current_epoch() < target {
+            if !self.advance() {
+                std::thread::yield_now();
+            }
+        }
+
+        // A collection that took the list before the epoch got here may judge
+        // it against an older epoch and push bags sealed before this call back.
+        // Collections that take the list from now on load the new epoch and
+        // free those bags themselves, so wait out the ones in flight on both
+        // sides of the final pass.
+        self.wait_for_gc();
+        self.gc();
+        self.wait_for_gc();
+    }
+
+    /// Wait until every `gc` that started before the call has finished,
+    /// without waiting for the ones that start later.
+    fn wait_for_gc(&self) {
+        loop {
+            let g = self.generation.load(Ordering::SeqCst);
+            // Generation `g + 1` reuses the other slot, which may still count
+            // collections from `g - 1`.
+            if !self.drain(g + 1, || self.generation.load(Ordering::SeqCst) != g) {
+                continue;
+            }
+            if self
+                .generation
+                .compare_exchange(g, g + 1, Ordering::SeqCst, Ordering::SeqCst)
+                .is_err()
+            {
+                continue;
+            }
+            // New collections count towards `g + 1` now, so this only waits
+            // for the ones that started before the switch. Whoever moves on
+            // to `g + 2` drains this slot first.
+            self.drain(g, || self.generation.load(Ordering::SeqCst) >= g + 2);
+            return;
+        }
+    }
+
+    /// Spin until no collection of generation `g` is in flight. Returns
+    /// `false` if `stop` says to give up first.
+    fn drain(&self, g: usize, stop: impl Fn() -> bool) -> bool {
+        while self.collecting[g % 2].load(Ordering::SeqCst) != 0 {
+            if stop() {
+                return false;
+            }
+            std::thread::yield_now();
+        }
+        true
+    }
+
+    /// Claims an inactive participant node, or pushes a new one if every node
+    /// is taken.
+    fn acquire_participant(&self) -> *const Participant {
+        for p in self.iter_participants() {
+            if p.active
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+            {
+                return p;
+            }
+        }
+
+        let node = Box::into_raw(Box::new(Participant {
+            epoch: AtomicUsize::new(INACTIVE),
+            active: AtomicBool::new(true),
+            next: AtomicPtr::new(ptr::null_mut()),
+        }));
+        let mut head = self.participants.load(Ordering::Acquire);
+        loop {
+            unsafe { (*node).next.store(head, Ordering::Relaxed) };
+            match self.participants.compare_exchange_weak(
+                head,
+                node,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return node,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    fn iter_participants(&self) -> impl Iterator<Item = &Participant> {
+        let mut node = self.participants.load(Ordering::Acquire);
+        std::iter::from_fn(move || {
+            // SAFETY: Participant nodes are only freed when the collector is
+            // dropped, which outlives this borrow.
+            let p = unsafe { node.as_ref()? };
+            node = p.next.load(Ordering::Acquire);
+            Some(p)
+        })
+    }
+
+    /// Try to advance the global epoch. Fails if some pinned thread lags
+    /// behind, or if another thread advanced it first.
     fn advance(&self) -> bool {
         let current = self.epoch.load(Ordering::Acquire);
 
-        let min_epoch = {
-            let threads = match self.threads.try_lock() {
-                Ok(t) => t,
-                Err(_) => return false,
-            };
-            threads
-                .iter()
-                .map(|t| t.load(Ordering::Acquire))
-                .filter(|&e| e != usize::MAX)
-                .min()
-                .unwrap_or(current)
-        };
+        // Pairs with the fence in `pin` so that a thread either shows up here
+        // as pinned or observes the pointers unlinked before this point.
+        fence(Ordering::SeqCst);
+        let min_epoch = self
+            .iter_participants()
+            .map(|p| p.epoch.load(Ordering::Acquire))
+            .filter(|&e| e != INACTIVE)
+            .min()
+            .unwrap_or(current);
 
-        if min_epoch >= current.saturating_sub(1) {
-            self.epoch.fetch_add(1, Ordering::Release);
-            true
-        } else {
-            false
-        }
+        min_epoch >= current.saturating_sub(1)
+            && self
+                .epoch
+                .compare_exchange(current, current + 1, Ordering::Release, Ordering::Relaxed)
+                .is_ok()
     }
 
-    /// Free garbage entries that are old enough to be safe. Drains reclaimable
-    /// entries under the lock, then runs destructors *outside* the lock to
-    /// avoid blocking concurrent `defer` calls.
+    /// Free the global bags that are old enough to be safe. Takes the whole
+    /// list in one swap, pushes back the bags that weren't old enough, then
+    /// runs destructors without touching shared state.
     fn gc(&self) {
-        let current = self.epoch.load(Ordering::Acquire);
-        let safe_epoch = current.saturating_sub(3);
-
-        // Take all entries out, release the lock quickly.
-        let entries: Vec<Garbage> = {
-            let mut list = match self.garbage.try_lock() {
-                Ok(l) => l,
-                Err(_) => return,
-            };
-            std::mem::take(&mut *list)
-        };
+        /// Counts the collection as finished even if a destructor panics.
+        struct InFlight<'a>(&'a AtomicUsize);
 
-        let mut remaining = Vec::new();
-        for g in entries {
-            if g.epoch <= safe_epoch {
-                unsafe { (g.deleter)(g.ptr) };
+        impl Drop for InFlight<'_> {
+            fn drop(&mut self) {
+                self.0.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+
+        let collecting = &self.collecting[self.generation.load(Ordering::SeqCst) % 2];
+        collecting.fetch_add(1, Ordering::SeqCst);
+        let _in_flight = InFlight(collecting);
+        let mut node = self.garbage.swap(ptr::null_mut(), Ordering::Acquire);
+        // Loaded after taking the list, so `synchronize` can tell which epoch
+        // the bags are judged against.
+        let current = self.epoch.load(Ordering::SeqCst);
+        let mut ripe = Vec::new();
+        let mut remaining: *mut SealedBag = ptr::null_mut();
+        let mut tail: *mut SealedBag = ptr::null_mut();
+
+        while !node.is_null() {
+            // SAFETY: The swap above gave this thread exclusive ownership of
+            // every bag in the list.
+            let next = unsafe { (*node).next };
+            if is_reclaimable(unsafe { (*node).epoch }, current) {
+                ripe.push(unsafe { Box::from_raw(node) });
             } else {
-                remaining.push(g);
+                unsafe { (*node).next = remaining };
+                if remaining.is_null() {
+                    tail = node;
+                }
+                remaining = node;
             }
+            node = next;
         }
 
-        // Put back entries that weren't old enough.
-        if !remaining.is_empty() {
-            let mut list = self.garbage.lock().unwrap();
-            remaining.append(&mut *list);
-            *list = remaining;
+        // Put back bags that weren't old enough before any destructor runs.
+        if !remaining.is_null() {
+            self.push_bags(remaining, tail);
         }
+        run_all(ripe.into_iter().flat_map(|bag| bag.garbage));
     }
 
-    /// Push a garbage entry.
-    fn defer(&self, garbage: Garbage) {
-        self.garbage.lock().unwrap().push(garbage);
+    /// Seal `garbage` with the current epoch and push it onto the global list.
+    fn push_bag(&self, garbage: Vec<Garbage>) {
+        let bag = Box::into_raw(Box::new(SealedBag {
+            epoch: self.current_epoch(),
+            garbage,
+            next: ptr::null_mut(),
+        }));
+        self.push_bags(bag, bag);
+    }
+
+    /// Push the chain of bags from `first` to `last` onto the global list.
+    fn push_bags(&self, first: *mut SealedBag, last: *mut SealedBag) {
+        let mut head = self.garbage.load(Ordering::Acquire);
+        loop {
+            unsafe { (*last).next = head };
+            match self.garbage.compare_exchange_weak(
+                head,
+                first,
+                Ordering::Release,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
     }
 
     /// Current epoch value.
@@ -142,36 +435,126 @@ impl Collector {
     }
 }
 
+impl Drop for Collector {
+    fn drop(&mut self) {
+        // Every handle holds an `Arc` to the collector, so no thread can be
+        // pinned and all remaining garbage is unreachable.
+        let mut bags = Vec::new();
+        let mut node = *self.garbage.get_mut();
+        while !node.is_null() {
+            let bag = unsafe { Box::from_raw(node) };
+            node = bag.next;
+            bags.push(bag);
+        }
+        run_all(bags.into_iter().flat_map(|bag| bag.garbage));
+
+        let mut node = *self.participants.get_mut();
+        while !node.is_null() {
+            let p = unsafe { Box::from_raw(node) };
+            node = p.next.load(Ordering::Relaxed);
+        }
+    }
+}
+
 /// Per-thread handle to a [`Collector`]. Provides [`pin`](LocalHandle::pin)
 /// for entering a critical section.
+///
+/// A handle can be moved to another thread while no guard is alive, but not
+/// shared between threads.
 pub struct LocalHandle {
     collector: Arc<Collector>,
-    epoch: Arc<AtomicUsize>,
+    /// This thread's node in the participant list, kept alive by `collector`.
+    participant: *const Participant,
+    /// Garbage retired by this thread, in retirement order.
+    bag: RefCell<Vec<Garbage>>,
+    /// Number of live guards, only the outermost one pins and unpins.
+    guards: Cell<usize>,
 }
 
+// SAFETY: The participant node is never freed while the collector lives, and
+// `collector` keeps it alive, so the pointer stays valid on any thread. Guards
+// borrow the handle, so it can only move while unpinned, and nothing else
+// about the participant or the bag is tied to the thread that registered it.
+unsafe impl Send for LocalHandle {}
+
 impl LocalHandle {
     /// Pin the current thread to the global epoch, returning an RAII
     /// [`Guard`]. While the guard is alive, no pointer retired *after* this
     /// epoch can be freed.
+    ///
+    /// Pinning is reentrant: a guard taken while another is alive keeps the
+    /// outer epoch, and the thread stays pinned until the last guard drops.
     pub fn pin(&self) -> Guard<'_> {
-        let epoch = self.collector.current_epoch();
-        self.epoch.store(epoch, Ordering::Release);
+        let guards = self.guards.get();
+        self.guards.set(guards + 1);
+        if guards == 0 {
+            let epoch = self.collector.current_epoch();
+            self.participant().epoch.store(epoch, Ordering::Relaxed);
+            // Make the pin visible before any shared pointer is read.
+            fence(Ordering::SeqCst);
+        }
         Guard { handle: self }
     }
+
+    fn participant(&self) -> &Participant {
+        // SAFETY: Participant nodes live as long as the collector, which
+        // `self.collector` keeps alive.
+        unsafe { &*self.participant }
+    }
+
+    /// Try to advance the epoch, then free the garbage of this thread and of
+    /// the global list that has become safe to free.
+    pub fn collect(&self) {
+        self.collector.advance();
+        self.collect_local();
+        self.collector.gc();
+    }
+
+    /// Add `garbage` to the local bag, sealing and pushing the bag to the
+    /// global list once it is full.
+    fn defer(&self, garbage: Garbage) {
+        let mut bag = self.bag.borrow_mut();
+        bag.push(garbage);
+        if bag.len() >= BAG_CAP {
+            let full = std::mem::replace(&mut *bag, Vec::with_capacity(BAG_CAP));
+            drop(bag);
+            self.collector.push_bag(full);
+        }
+    }
+
+    /// Free the entries of the local bag that are old enough to be safe.
+    fn collect_local(&self) {
+        let current = self.collector.current_epoch();
+
+        // Entries are in retirement order, so the reclaimable ones form a
+        // prefix. Split it off and release the bag before running it, so
+        // destructors can retire more garbage.
+        let mut bag = self.bag.borrow_mut();
+        let ripe = bag.partition_point(|g| is_reclaimable(g.epoch, current));
+        let rest = bag.split_off(ripe);
+        let entries = std::mem::replace(&mut *bag, rest);
+        drop(bag);
+        run_all(entries.into_iter());
+    }
 }
 
 impl Drop for LocalHandle {
     fn drop(&mut self) {
-        // Mark as inactive.
-        self.epoch.store(usize::MAX, Ordering::Release);
-        // Remove from registry.
-        let mut threads = self.collector.threads.lock().unwrap();
-        threads.retain(|t| !Arc::ptr_eq(t, &self.epoch));
+        // Hand leftover garbage to the collector.
+        let bag = std::mem::take(self.bag.get_mut());
+        if !bag.is_empty() {
+            self.collector.push_bag(bag);
+        }
+        // Mark as inactive and release the participant node for reuse.
+        let participant = self.participant();
+        participant.epoch.store(INACTIVE, Ordering::Release);
+        participant.active.store(false, Ordering::Release);
     }
 }
 
 /// RAII proof that the current thread is pinned. Provides
-/// [`defer_destroy`](Guard::defer_destroy) to retire pointers.
+/// [`defer_destroy`](Guard::defer_destroy) to retire pointers and
+/// [`defer`](Guard::defer) to run arbitrary clean-up after a grace period.
 pub struct Guard<'a> {
     handle: &'a LocalHandle,
 }
@@ -180,32 +563,437 @@ impl Guard<'_> {
     /// Schedule `ptr` (which must have been allocated via `Box::into_raw`) to
     /// be freed once it is safe to do so.
     pub fn defer_destroy<T>(&self, ptr: *mut T) {
+        let ptr = ptr as *mut u8;
+        unsafe { self.defer_unchecked(move || drop_box::<T>(ptr)) };
+    }
+
+    /// Seal the local bag, even if it is not full, and hand it to the global
+    /// list where any thread can free it, then collect what is already safe.
+    ///
+    /// Together with [`Collector::synchronize`] this frees everything retired
+    /// so far without waiting for the bag to fill up.
+    pub fn flush(&self) {
+        let bag = std::mem::replace(
+            &mut *self.handle.bag.borrow_mut(),
+            Vec::with_capacity(BAG_CAP),
+        );
+        if !bag.is_empty() {
+            self.handle.collector.push_bag(bag);
+        }
+        self.handle.collector.advance();
+        self.handle.collector.gc();
+    }
+
+    /// Schedule `f` to run once every thread pinned now has unpinned, e.g. to
+    /// return a node to a pool or decrement a reference count after a grace
+    /// period. `f` may run on any thread.
+    ///
+    /// If `f` panics, the panic surfaces from whichever call ran it, after the
+    /// rest of the garbage collected alongside has run.
+    pub fn defer<F: FnOnce() + Send + 'static>(&self, f: F) {
+        unsafe { self.defer_unchecked(f) };
+    }
+
+    /// Like [`defer`](Guard::defer) without the `Send` and `'static` bounds.
+    ///
+    /// # Safety
+    ///
+    /// `f` may run on another thread and at any later time, including when
+    /// the [`Collector`] is dropped, so whatever it captures must still be
+    /// valid and safe to use from that thread by then.
+    pub unsafe fn defer_unchecked<F: FnOnce()>(&self, f: F) {
         let epoch = self.handle.collector.current_epoch();
-        self.handle.collector.defer(Garbage {
-            epoch,
-            ptr: ptr as *mut u8,
-            deleter: drop_box::<T>,
-        });
+        self.handle.defer(Garbage::new(epoch, f));
     }
 }
 
 impl Drop for Guard<'_> {
     fn drop(&mut self) {
+        let guards = self.handle.guards.get() - 1;
+        self.handle.guards.set(guards);
+        if guards > 0 {
+            // An outer guard still relies on the pin.
+            return;
+        }
+
         // Unpin.
-        self.handle.epoch.store(usize::MAX, Ordering::Release);
+        self.handle
+            .participant()
+            .epoch
+            .store(INACTIVE, Ordering::Release);
         // Try to advance + collect.
         if self.handle.collector.advance() {
+            self.handle.collect_local();
             self.handle.collector.gc();
         }
     }
 }
 
+/// Mask of the low pointer bits that are always zero for a `T` allocation and
+/// can carry a tag.
+const fn tag_mask<T>() -> usize {
+    align_of::<T>() - 1
+}
+
+/// A pointer that can be stored in an [`Atomic`]: either an [`Owned`] or a
+/// [`Shared`].
+pub trait Pointer<T> {
+    /// Converts into the tagged address, giving up ownership if any.
+    fn into_usize(self) -> usize;
+
+    /// Rebuilds the pointer from a tagged address.
+    ///
+    /// # Safety
+    ///
+    /// `data` must come from [`into_usize`](Pointer::into_usize) on the same
+    /// pointer type, and an [`Owned`] may only be rebuilt once.
+    unsafe fn from_usize(data: usize) -> Self;
+}
+
+/// An atomic pointer to a heap-allocated `T` whose low bits can hold a tag.
+///
+/// Loads need a [`Guard`] and return a [`Shared`] that cannot outlive it, so
+/// a pointer read inside a critical section cannot be used after the thread
+/// unpins. Dropping an `Atomic` does not free its pointee.
+pub struct Atomic<T> {
+    data: AtomicUsize,
+    _marker: PhantomData<*mut T>,
+}
+
+// SAFETY: An `Atomic` hands out `&T` to other threads and may move the `T`
+// between them, like `Arc<T>`.
+unsafe impl<T: Send + Sync> Send for Atomic<T> {}
+unsafe impl<T: Send + Sync> Sync for Atomic<T> {}
+
+impl<T> Atomic<T> {
+    /// Returns a null pointer.
+    pub const fn null() -> Self {
+        Self {
+            data: AtomicUsize::new(0),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Allocates `value` on the heap and points to it.
+    pub fn new(value: T) -> Self {
+        Self::from(Owned::new(value))
+    }
+
+    /// Loads the pointer, valid for as long as `guard` stays pinned.
+    pub fn load<'g>(&self, order: Ordering, _guard: &'g Guard<'_>) -> Shared<'g, T> {
+        unsafe { Shared::from_usize(self.data.load(order)) }
+    }
+
+    /// Stores `new`, taking ownership of it if it is an [`Owned`].
+    pub fn store<P: Pointer<T>>(&self, new: P, order: Ordering) {
+        self.data.store(new.into_usize(), order);
+    }
+
+    /// Stores `new` and returns the previous pointer.
+    pub fn swap<'g, P: Pointer<T>>(
+        &self,
+        new: P,
+        order: Ordering,
+        _guard: &'g Guard<'_>,
+    ) -> Shared<'g, T> {
+        unsafe { Shared::from_usize(self.data.swap(new.into_usize(), order)) }
+    }
+
+    /// Stores `new` if the pointer, tag included, equals `current`.
+    ///
+    /// On success returns `new` as a [`Shared`]. On failure returns the
+    /// actual pointer along with `new`, so an [`Owned`] can be retried
+    /// without reallocating.
+    pub fn compare_exchange<'g, P: Pointer<T>>(
+        &self,
+        current: Shared<'_, T>,
+        new: P,
+        success: Ordering,
+        failure: Ordering,
+        _guard: &'g Guard<'_>,
+    ) -> Result<Shared<'g, T>, CompareExchangeError<'g, T, P>> {
+        let new = new.into_usize();
+        match self
+            .data
+            .compare_exchange(current.data, new, success, failure)
+        {
+            Ok(_) => Ok(unsafe { Shared::from_usize(new) }),
+            Err(actual) => Err(CompareExchangeError {
+                current: unsafe { Shared::from_usize(actual) },
+                new: unsafe { P::from_usize(new) },
+            }),
+        }
+    }
+
+    /// Like [`compare_exchange`](Atomic::compare_exchange) but may fail
+    /// spuriously, which is cheaper in a retry loop on some platforms.
+    pub fn compare_exchange_weak<'g, P: Pointer<T>>(
+        &self,
+        current: Shared<'_, T>,
+        new: P,
+        success: Ordering,
+        failure: Ordering,
+        _guard: &'g Guard<'_>,
+    ) -> Result<Shared<'g, T>, CompareExchangeError<'g, T, P>> {
+        let new = new.into_usize();
+        match self
+            .data
+            .compare_exchange_weak(current.data, new, success, failure)
+        {
+            Ok(_) => Ok(unsafe { Shared::from_usize(new) }),
+            Err(actual) => Err(CompareExchangeError {
+                current: unsafe { Shared::from_usize(actual) },
+                new: unsafe { P::from_usize(new) },
+            }),
+        }
+    }
+
+    /// Takes ownership of the pointee.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may still access the pointee, and the pointer must not
+    /// be null.
+    pub unsafe fn into_owned(self) -> Owned<T> {
+        unsafe { Owned::from_usize(self.data.into_inner()) }
+    }
+}
+
+impl<T> Default for Atomic<T> {
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<T> From<Owned<T>> for Atomic<T> {
+    fn from(owned: Owned<T>) -> Self {
+        Self {
+            data: AtomicUsize::new(owned.into_usize()),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> From<Shared<'_, T>> for Atomic<T> {
+    fn from(shared: Shared<'_, T>) -> Self {
+        Self {
+            data: AtomicUsize::new(shared.into_usize()),
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Error of a failed [`Atomic::compare_exchange`].
+pub struct CompareExchangeError<'g, T, P: Pointer<T>> {
+    /// The pointer found in the atomic.
+    pub current: Shared<'g, T>,
+    /// The pointer that was to be stored, handed back to the caller.
+    pub new: P,
+}
+
+impl<T, P: Pointer<T>> std::fmt::Debug for CompareExchangeError<'_, T, P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompareExchangeError")
+            .field("current", &self.current)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An owned, tagged heap allocation, like a `Box<T>` with a tag.
+pub struct Owned<T> {
+    data: usize,
+    _marker: PhantomData<Box<T>>,
+}
+
+impl<T> Owned<T> {
+    /// Allocates `value` on the heap.
+    pub fn new(value: T) -> Self {
+        Self::from(Box::new(value))
+    }
+
+    /// Returns the tag stored in the low bits.
+    pub fn tag(&self) -> usize {
+        self.data & tag_mask::<T>()
+    }
+
+    /// Returns the same pointer with its tag set to `tag`, truncated to the
+    /// bits the alignment of `T` leaves free.
+    pub fn with_tag(self, tag: usize) -> Self {
+        let data = self.into_usize();
+        unsafe { Self::from_usize((data & !tag_mask::<T>()) | (tag & tag_mask::<T>())) }
+    }
+
+    /// Converts into a [`Shared`] valid while `guard` is pinned. The
+    /// allocation is no longer freed automatically.
+    pub fn into_shared<'g>(self, _guard: &'g Guard<'_>) -> Shared<'g, T> {
+        unsafe { Shared::from_usize(self.into_usize()) }
+    }
+
+    /// Converts back into a `Box`, dropping the tag.
+    pub fn into_box(self) -> Box<T> {
+        let raw = (self.into_usize() & !tag_mask::<T>()) as *mut T;
+        unsafe { Box::from_raw(raw) }
+    }
+}
+
+impl<T> From<Box<T>> for Owned<T> {
+    fn from(b: Box<T>) -> Self {
+        unsafe { Self::from_usize(Box::into_raw(b) as usize) }
+    }
+}
+
+impl<T> Pointer<T> for Owned<T> {
+    fn into_usize(self) -> usize {
+        let data = self.data;
+        std::mem::forget(self);
+        data
+    }
+
+    unsafe fn from_usize(data: usize) -> Self {
+        debug_assert!(data & !tag_mask::<T>() != 0, "null Owned");
+        Self {
+            data,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> std::ops::Deref for Owned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*((self.data & !tag_mask::<T>()) as *const T) }
+    }
+}
+
+impl<T> std::ops::DerefMut for Owned<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *((self.data & !tag_mask::<T>()) as *mut T) }
+    }
+}
+
+impl<T> Drop for Owned<T> {
+    fn drop(&mut self) {
+        let raw = (self.data & !tag_mask::<T>()) as *mut T;
+        unsafe { drop(Box::from_raw(raw)) };
+    }
+}
+
+/// A tagged pointer loaded from an [`Atomic`], valid while the guard `'g`
+/// keeps the thread pinned.
+pub struct Shared<'g, T> {
+    data: usize,
+    _marker: PhantomData<(&'g (), *const T)>,
+}
+
+impl<T> Clone for Shared<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Shared<'_, T> {}
+
+impl<T> PartialEq for Shared<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.data == other.data
+    }
+}
+
+impl<T> Eq for Shared<'_, T> {}
+
+impl<T> std::fmt::Debug for Shared<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Shared")
+            .field("raw", &self.as_raw())
+            .field("tag", &self.tag())
+            .finish()
+    }
+}
+
+impl<'g, T> Shared<'g, T> {
+    /// Returns a null pointer.
+    pub fn null() -> Self {
+        unsafe { Self::from_usize(0) }
+    }
+
+    /// Returns `true` if the pointer, ignoring the tag, is null.
+    pub fn is_null(&self) -> bool {
+        self.as_raw().is_null()
+    }
+
+    /// Returns the pointer without its tag, e.g. to pass to
+    /// [`Guard::defer_destroy`].
+    pub fn as_raw(&self) -> *mut T {
+        (self.data & !tag_mask::<T>()) as *mut T
+    }
+
+    /// Returns the tag stored in the low bits.
+    pub fn tag(&self) -> usize {
+        self.data & tag_mask::<T>()
+    }
+
+    /// Returns the same pointer with its tag set to `tag`, truncated to the
+    /// bits the alignment of `T` leaves free.
+    pub fn with_tag(&self, tag: usize) -> Self {
+        unsafe { Self::from_usize((self.data & !tag_mask::<T>()) | (tag & tag_mask::<T>())) }
+    }
+
+    /// Dereferences the pointer.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must be non-null and the pointee must not have been
+    /// retired before the guard was pinned.
+    pub unsafe fn deref(&self) -> &'g T {
+        unsafe { &*self.as_raw() }
+    }
+
+    /// Dereferences the pointer, or returns `None` if it is null.
+    ///
+    /// # Safety
+    ///
+    /// As for [`deref`](Shared::deref), apart from nullness.
+    pub unsafe fn as_ref(&self) -> Option<&'g T> {
+        unsafe { self.as_raw().as_ref() }
+    }
+
+    /// Takes ownership of the pointee.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must be non-null and no other thread may still access it,
+    /// for instance because it was unlinked and never published again.
+    pub unsafe fn into_owned(self) -> Owned<T> {
+        unsafe { Owned::from_usize(self.data) }
+    }
+}
+
+impl<T> Pointer<T> for Shared<'_, T> {
+    fn into_usize(self) -> usize {
+        self.data
+    }
+
+    unsafe fn from_usize(data: usize) -> Self {
+        Self {
+            data,
+            _marker: PhantomData,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::sync::atomic::AtomicUsize;
     use std::thread;
 
+    fn active_participants(c: &Collector) -> usize {
+        c.iter_participants()
+            .filter(|p| p.active.load(Ordering::Relaxed))
+            .count()
+    }
+
     #[test]
     fn pin_unpin_advances_epoch() {
         let c = Collector::new();
@@ -242,12 +1030,69 @@ mod tests {
             guard.defer_destroy(ptr);
         }
 
-        // Pump the collector to flush garbage.
-        for _ in 0..10 {
-            let _g = h.pin();
+        // Whatever is still in the local bag is freed once this thread
+        // collects after a grace period.
+        c.synchronize();
+        h.collect();
+        assert_eq!(DROP_COUNT.load(Ordering::Relaxed), 100);
+    }
+
+    #[test]
+    fn full_bags_are_reclaimed_globally() {
+        static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        struct Tracked;
+        impl Drop for Tracked {
+            fn drop(&mut self) {
+                DROP_COUNT.fetch_add(1, Ordering::Relaxed);
+            }
         }
 
-        assert_eq!(DROP_COUNT.load(Ordering::Relaxed), 100);
+        let c = Collector::new();
+        let h1 = c.register();
+        let h2 = c.register();
+
+        {
+            let guard = h1.pin();
+            for _ in 0..BAG_CAP {
+                guard.defer_destroy(Box::into_raw(Box::new(Tracked)));
+            }
+        }
+        // The full bag left the thread.
+        assert!(h1.bag.borrow().is_empty());
+        assert!(!c.garbage.load(Ordering::Relaxed).is_null());
+
+        // Another thread frees it.
+        c.synchronize();
+        h2.collect();
+        assert_eq!(DROP_COUNT.load(Ordering::Relaxed), BAG_CAP);
+        assert!(c.garbage.load(Ordering::Relaxed).is_null());
+    }
+
+    #[test]
+    fn collector_drop_frees_leftovers() {
+        static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        struct Tracked;
+        impl Drop for Tracked {
+            fn drop(&mut self) {
+                DROP_COUNT.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let c = Collector::new();
+        let h = c.register();
+        {
+            let guard = h.pin();
+            for _ in 0..10 {
+                guard.defer_destroy(Box::into_raw(Box::new(Tracked)));
+            }
+        }
+        drop(h);
+        assert_eq!(DROP_COUNT.load(Ordering::Relaxed), 0);
+
+        drop(c);
+        assert_eq!(DROP_COUNT.load(Ordering::Relaxed), 10);
     }
 
     #[test]
@@ -256,13 +1101,44 @@ mod tests {
 
         let h1 = c.register();
         let h2 = c.register();
-        assert_eq!(c.threads.lock().unwrap().len(), 2);
+        assert_eq!(active_participants(&c), 2);
 
         drop(h1);
-        assert_eq!(c.threads.lock().unwrap().len(), 1);
+        assert_eq!(active_participants(&c), 1);
+
+        // The released node is reused instead of growing the list.
+        let h3 = c.register();
+        assert_eq!(active_participants(&c), 2);
+        assert_eq!(c.iter_participants().count(), 2);
 
         drop(h2);
-        assert_eq!(c.threads.lock().unwrap().len(), 0);
+        drop(h3);
+        assert_eq!(active_participants(&c), 0);
+    }
+
+    #[test]
+    fn handle_moves_between_threads() {
+        let c = Collector::new();
+        let h = c.register();
+        let freed = Arc::new(AtomicUsize::new(0));
+
+        let counter = Arc::clone(&freed);
+        h.pin().defer(move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+
+        // Retire on one thread, finish on another.
+        let h = thread::spawn(move || {
+            h.pin().flush();
+            h
+        })
+        .join()
+        .unwrap();
+
+        c.synchronize();
+        assert_eq!(freed.load(Ordering::Relaxed), 1);
+        drop(h);
+        assert_eq!(active_participants(&c), 0);
     }
 
     #[test]
@@ -282,10 +1158,7 @@ mod tests {
                         let ptr = Box::into_raw(Box::new(42u64));
                         guard.defer_destroy(ptr);
                     }
-                    // Flush.
-                    for _ in 0..10 {
-                        let _g = h.pin();
-                    }
+                    h.pin().flush();
                 })
             })
             .collect();
@@ -295,6 +1168,364 @@ mod tests {
         }
 
         // All threads deregistered.
-        assert_eq!(c.threads.lock().unwrap().len(), 0);
+        assert_eq!(active_participants(&c), 0);
+
+        // Everything they retired is global now.
+        c.synchronize();
+        assert!(c.garbage.load(Ordering::Relaxed).is_null());
+    }
+
+    #[test]
+    fn nested_guards_keep_thread_pinned() {
+        let c = Collector::new();
+        let h = c.register();
+        let h2 = c.register();
+        let freed = Arc::new(AtomicUsize::new(0));
+
+        let outer = h.pin();
+        let epoch = h.participant().epoch.load(Ordering::Relaxed);
+        let counter = Arc::clone(&freed);
+        outer.defer(move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+
+        {
+            let inner = h.pin();
+            assert_eq!(h.participant().epoch.load(Ordering::Relaxed), epoch);
+            drop(inner);
+        }
+
+        // Dropping the inner guard must not unpin the outer one, or these
+        // would free the value while `outer` can still reach it.
+        assert_eq!(h.participant().epoch.load(Ordering::Relaxed), epoch);
+        for _ in 0..10 {
+            h2.pin().flush();
+            h.collect();
+        }
+        assert_eq!(freed.load(Ordering::Relaxed), 0);
+
+        drop(outer);
+        assert_eq!(h.participant().epoch.load(Ordering::Relaxed), INACTIVE);
+        c.synchronize();
+        h.collect();
+        assert_eq!(freed.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn synchronize_races_with_gc() {
+        let c = Collector::new();
+        let freed = Arc::new(AtomicUsize::new(0));
+        let stop = AtomicBool::new(false);
+
+        thread::scope(|s| {
+            for _ in 0..3 {
+                s.spawn(|| {
+                    let h = c.register();
+                    while !stop.load(Ordering::Relaxed) {
+                        h.collect();
+                    }
+                });
+            }
+
+            let h = c.register();
+            for round in 1..=200 {
+                let guard = h.pin();
+                for _ in 0..10 {
+                    let counter = Arc::clone(&freed);
+                    guard.defer(move || {
+                        counter.fetch_add(1, Ordering::Relaxed);
+                    });
+                }
+                guard.flush();
+                drop(guard);
+
+                c.synchronize();
+                assert_eq!(freed.load(Ordering::Relaxed), 10 * round);
+            }
+            stop.store(true, Ordering::Relaxed);
+        });
+    }
+
+    #[test]
+    fn synchronize_with_busy_collector() {
+        let c = Collector::new();
+        let stop = AtomicBool::new(false);
+
+        thread::scope(|s| {
+            // Keeps a collection in flight almost all the time, which must
+            // not hold up the synchronizers below.
+            s.spawn(|| {
+                let h = c.register();
+                while !stop.load(Ordering::Relaxed) {
+                    h.pin().flush();
+                }
+            });
+
+            let synchronizers: Vec<_> = (0..2)
+                .map(|_| {
+                    s.spawn(|| {
+                        let h = c.register();
+                        let freed = Arc::new(AtomicUsize::new(0));
+                        for round in 1..=100 {
+                            let counter = Arc::clone(&freed);
+                            let guard = h.pin();
+                            guard.defer(move || {
+                                counter.fetch_add(1, Ordering::Relaxed);
+                            });
+                            guard.flush();
+                            drop(guard);
+
+                            c.synchronize();
+                            assert_eq!(freed.load(Ordering::Relaxed), round);
+                        }
+                    })
+                })
+                .collect();
+
+            for t in synchronizers {
+                t.join().unwrap();
+            }
+            stop.store(true, Ordering::Relaxed);
+        });
+    }
+
+    #[test]
+    fn synchronize_waits_for_pinned_threads() {
+        let c = Collector::new();
+        let h = c.register();
+        let freed = AtomicUsize::new(0);
+        let done = AtomicBool::new(false);
+
+        let guard = h.pin();
+        let counter = Arc::new(AtomicUsize::new(0));
+        let retired = Arc::clone(&counter);
+        guard.defer(move || {
+            retired.fetch_add(1, Ordering::Relaxed);
+        });
+        guard.flush();
+
+        thread::scope(|s| {
+            s.spawn(|| {
+                c.synchronize();
+                freed.store(counter.load(Ordering::Relaxed), Ordering::Relaxed);
+                done.store(true, Ordering::Release);
+            });
+
+            thread::sleep(std::time::Duration::from_millis(50));
+            assert!(!done.load(Ordering::Acquire));
+            drop(guard);
+        });
+
+        assert_eq!(freed.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn atomic_load_store_swap() {
+        let c = Collector::new();
+        let h = c.register();
+        let guard = h.pin();
+
+        let a = Atomic::new(1u64);
+        let first = a.load(Ordering::Acquire, &guard);
+        assert_eq!(unsafe { *first.deref() }, 1);
+
+        let old = a.swap(Owned::new(2), Ordering::AcqRel, &guard);
+        assert_eq!(old, first);
+        assert_eq!(
+            unsafe { a.load(Ordering::Acquire, &guard).as_ref() },
+            Some(&2)
+        );
+        guard.defer_destroy(old.as_raw());
+
+        let null = Atomic::<u64>::null();
+        assert!(null.load(Ordering::Acquire, &guard).is_null());
+        null.store(Shared::null().with_tag(1), Ordering::Release);
+        let tagged = null.load(Ordering::Acquire, &guard);
+        assert!(tagged.is_null());
+        assert_eq!(tagged.tag(), 1);
+
+        drop(unsafe { a.into_owned() });
+    }
+
+    #[test]
+    fn compare_exchange_returns_ownership() {
+        static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        struct Tracked(u32);
+        impl Drop for Tracked {
+            fn drop(&mut self) {
+                DROP_COUNT.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let c = Collector::new();
+        let h = c.register();
+        let guard = h.pin();
+
+        let a = Atomic::new(Tracked(1));
+        let current = a.load(Ordering::Acquire, &guard);
+
+        // A stale expected value fails and hands the allocation back.
+        let err = a
+            .compare_exchange(
+                current.with_tag(1),
+                Owned::new(Tracked(2)),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                &guard,
+            )
+            .unwrap_err();
+        assert_eq!(err.current, current);
+        assert_eq!(err.new.0, 2);
+
+        let new = a
+            .compare_exchange(
+                current,
+                err.new,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                &guard,
+            )
+            .unwrap();
+        assert_eq!(unsafe { new.deref() }.0, 2);
+        assert_eq!(DROP_COUNT.load(Ordering::Relaxed), 0);
+
+        drop(unsafe { current.into_owned() });
+        drop(unsafe { a.into_owned() });
+        assert_eq!(DROP_COUNT.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn pointer_tags() {
+        let c = Collector::new();
+        let h = c.register();
+        let guard = h.pin();
+
+        // `u64` leaves three low bits free.
+        let owned = Owned::new(7u64).with_tag(5);
+        assert_eq!(owned.tag(), 5);
+        assert_eq!(*owned, 7);
+
+        let shared = owned.into_shared(&guard);
+        assert_eq!(shared.tag(), 5);
+        assert_eq!(shared.with_tag(9).tag(), 1);
+        assert_eq!(shared.with_tag(0).as_raw(), shared.as_raw());
+        assert_eq!(unsafe { *shared.deref() }, 7);
+
+        let owned = unsafe { shared.into_owned() };
+        assert_eq!(*owned.into_box(), 7);
+
+        // A byte has no spare bits.
+        assert_eq!(Owned::new(1u8).with_tag(1).tag(), 0);
+    }
+
+    #[test]
+    fn deferred_closures_run_once() {
+        let c = Collector::new();
+        let h = c.register();
+        let small = Arc::new(AtomicUsize::new(0));
+        let large = Arc::new(AtomicUsize::new(0));
+
+        {
+            let guard = h.pin();
+            let counter = Arc::clone(&small);
+            guard.defer(move || {
+                counter.fetch_add(1, Ordering::Relaxed);
+            });
+
+            // Too big to store inline, so it is boxed.
+            let counter = Arc::clone(&large);
+            let payload = [7u64; 16];
+            guard.defer(move || {
+                counter.fetch_add(payload.iter().sum::<u64>() as usize, Ordering::Relaxed);
+            });
+            assert_eq!(small.load(Ordering::Relaxed), 0);
+        }
+
+        h.pin().flush();
+        c.synchronize();
+        assert_eq!(small.load(Ordering::Relaxed), 1);
+        assert_eq!(large.load(Ordering::Relaxed), 7 * 16);
+
+        // Nothing is left to run twice when the collector goes away.
+        drop(h);
+        drop(c);
+        assert_eq!(Arc::strong_count(&small), 1);
+        assert_eq!(Arc::strong_count(&large), 1);
+    }
+
+    #[test]
+    fn panicking_closure_does_not_leak_the_rest() {
+        use std::panic::{AssertUnwindSafe, catch_unwind};
+
+        let c = Collector::new();
+        let h = c.register();
+        let ran = Arc::new(AtomicUsize::new(0));
+
+        let retire = |guard: &Guard<'_>| {
+            let counter = Arc::clone(&ran);
+            guard.defer(move || {
+                counter.fetch_add(1, Ordering::Relaxed);
+            });
+            guard.defer(|| panic!("deferred closure panicked"));
+            // Boxed, so skipping it would leak the allocation.
+            let counter = Arc::clone(&ran);
+            let payload = [1u64; 16];
+            guard.defer(move || {
+                counter.fetch_add(payload.len() - 15, Ordering::Relaxed);
+            });
+        };
+
+        // Through the global list.
+        let guard = h.pin();
+        retire(&guard);
+        guard.flush();
+        drop(guard);
+        assert!(catch_unwind(AssertUnwindSafe(|| c.synchronize())).is_err());
+        assert_eq!(ran.load(Ordering::Relaxed), 2);
+
+        // The collection still counts as finished, so this doesn't hang.
+        c.synchronize();
+
+        // Through the local bag.
+        retire(&h.pin());
+        c.synchronize();
+        assert!(catch_unwind(AssertUnwindSafe(|| h.collect())).is_err());
+        assert_eq!(ran.load(Ordering::Relaxed), 4);
+        assert!(h.bag.borrow().is_empty());
+
+        drop(h);
+        drop(c);
+        assert_eq!(Arc::strong_count(&ran), 1);
+    }
+
+    #[test]
+    fn small_closures_are_inline() {
+        let (a, b, c) = (Arc::new(1), 2usize, 3u64);
+        let f = move || drop((a, b, c));
+        assert!(fits_inline::<*mut u8>());
+        assert!(fits_inline_of(&f));
+        assert!(!fits_inline::<[u64; 4]>());
+    }
+
+    fn fits_inline_of<F>(_: &F) -> bool {
+        fits_inline::<F>()
+    }
+
+    #[test]
+    fn defer_unchecked_borrows() {
+        let c = Collector::new();
+        let h = c.register();
+        let mut ran = false;
+        {
+            let guard = h.pin();
+            let ran = &mut ran as *mut bool;
+            // SAFETY: `ran` outlives the collector, which runs the closure at
+            // the latest when it is dropped below.
+            unsafe { guard.defer_unchecked(move || *ran = true) };
+        }
+        drop(h);
+        drop(c);
+        assert!(ran);
     }
 }
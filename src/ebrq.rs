@@ -1,16 +1,13 @@
 //! Lock-free unbounded MPMC queue (Michael-Scott) backed by epoch-based
 //! reclamation from [`crate::ebr`].
 
-use std::{
-    ptr,
-    sync::atomic::{AtomicPtr, Ordering},
-};
+use std::sync::atomic::Ordering;
 
-use crate::ebr::LocalHandle;
+use crate::ebr::{Atomic, LocalHandle, Owned, Pointer, Shared};
 
 struct Node<T> {
     value: Option<T>,
-    next: AtomicPtr<Node<T>>,
+    next: Atomic<Node<T>>,
 }
 
 /// A lock-free unbounded FIFO queue.
@@ -18,68 +15,83 @@ struct Node<T> {
 /// Operations require a [`LocalHandle`] obtained from an
 /// [`ebr::Collector`](crate::ebr::Collector).
 pub struct Queue<T> {
-    head: AtomicPtr<Node<T>>,
-    tail: AtomicPtr<Node<T>>,
+    head: Atomic<Node<T>>,
+    tail: Atomic<Node<T>>,
 }
 
+// SAFETY: The queue never hands out references to its values, it only moves
+// them in through `enqueue` and out through `dequeue`, where the thread that
+// wins the head CAS is the only one to touch the value. Threads sharing the
+// queue therefore never access a `T` concurrently, so `T: Send` is enough for
+// both impls, the same as for a channel. `Atomic` asks for `T: Sync` as well
+// because it cannot know that its pointee is never shared.
+unsafe impl<T: Send> Send for Queue<T> {}
+unsafe impl<T: Send> Sync for Queue<T> {}
+
 impl<T> Queue<T> {
     /// Create an empty queue with a sentinel node.
     pub fn new() -> Self {
-        let sentinel = Box::into_raw(Box::new(Node {
+        let sentinel = Owned::new(Node::<T> {
             value: None,
-            next: AtomicPtr::new(ptr::null_mut()),
-        }));
+            next: Atomic::null(),
+        });
+        // Both ends start at the sentinel.
+        let sentinel = unsafe { Shared::from_usize(sentinel.into_usize()) };
 
         Self {
-            head: AtomicPtr::new(sentinel),
-            tail: AtomicPtr::new(sentinel),
+            head: Atomic::from(sentinel),
+            tail: Atomic::from(sentinel),
         }
     }
 
     /// Append `value` to the back of the queue.
     pub fn enqueue(&self, value: T, handle: &LocalHandle) {
-        let _guard = handle.pin();
+        let guard = handle.pin();
 
-        let new_node = Box::into_raw(Box::new(Node {
+        let mut new_node = Owned::new(Node {
             value: Some(value),
-            next: AtomicPtr::new(ptr::null_mut()),
-        }));
+            next: Atomic::null(),
+        });
 
         loop {
-            let tail = self.tail.load(Ordering::Acquire);
-            let next = unsafe { (*tail).next.load(Ordering::Acquire) };
+            let tail = self.tail.load(Ordering::Acquire, &guard);
+            let next = unsafe { tail.deref() }.next.load(Ordering::Acquire, &guard);
 
-            if tail != self.tail.load(Ordering::Acquire) {
+            if tail != self.tail.load(Ordering::Acquire, &guard) {
                 continue;
             }
 
             if next.is_null() {
                 // Tail is the last node — try to append.
-                unsafe {
-                    if (*tail)
-                        .next
-                        .compare_exchange_weak(
-                            ptr::null_mut(),
-                            new_node,
-                            Ordering::Release,
-                            Ordering::Acquire,
-                        )
-                        .is_ok()
-                    {
+                match unsafe { tail.deref() }.next.compare_exchange_weak(
+                    Shared::null(),
+                    new_node,
+                    Ordering::Release,
+                    Ordering::Acquire,
+                    &guard,
+                ) {
+                    Ok(new_node) => {
                         let _ = self.tail.compare_exchange(
                             tail,
                             new_node,
                             Ordering::Release,
                             Ordering::Acquire,
+                            &guard,
                         );
                         return;
                     }
+                    // Lost the race — take the node back and retry.
+                    Err(e) => new_node = e.new,
                 }
             } else {
                 // Tail is behind — help advance it.
-                let _ =
-                    self.tail
-                        .compare_exchange(tail, next, Ordering::Release, Ordering::Acquire);
+                let _ = self.tail.compare_exchange(
+                    tail,
+                    next,
+                    Ordering::Release,
+                    Ordering::Acquire,
+                    &guard,
+                );
             }
         }
     }
@@ -89,11 +101,11 @@ impl<T> Queue<T> {
         let guard = handle.pin();
 
         loop {
-            let head = self.head.load(Ordering::Acquire);
-            let tail = self.tail.load(Ordering::Acquire);
-            let next = unsafe { (*head).next.load(Ordering::Acquire) };
+            let head = self.head.load(Ordering::Acquire, &guard);
+            let tail = self.tail.load(Ordering::Acquire, &guard);
+            let next = unsafe { head.deref() }.next.load(Ordering::Acquire, &guard);
 
-            if head != self.head.load(Ordering::Acquire) {
+            if head != self.head.load(Ordering::Acquire, &guard) {
                 continue;
             }
 
@@ -102,25 +114,33 @@ impl<T> Queue<T> {
                     return None;
                 }
                 // Tail is behind — help advance it.
-                let _ =
-                    self.tail
-                        .compare_exchange(tail, next, Ordering::Release, Ordering::Acquire);
-            } else {
-                if self
-                    .head
-                    .compare_exchange_weak(head, next, Ordering::Release, Ordering::Acquire)
-                    .is_ok()
-                {
-                    // CAS succeeded — exclusive access to next's value.
-                    let value = unsafe { (*next).value.take() };
-                    guard.defer_destroy(head);
-                    return value;
-                }
+                let _ = self.tail.compare_exchange(
+                    tail,
+                    next,
+                    Ordering::Release,
+                    Ordering::Acquire,
+                    &guard,
+                );
+            } else if self
+                .head
+                .compare_exchange_weak(head, next, Ordering::Release, Ordering::Acquire, &guard)
+                .is_ok()
+            {
+                // CAS succeeded — exclusive access to next's value.
+                let value = unsafe { (*next.as_raw()).value.take() };
+                guard.defer_destroy(head.as_raw());
+                return value;
             }
         }
     }
 }
 
+impl<T> Default for Queue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -145,6 +165,13 @@ mod tests {
         assert_eq!(q.dequeue(&h), None);
     }
 
+    #[test]
+    fn shareable_with_send_values() {
+        fn assert_send_sync<Q: Send + Sync>() {}
+        // `Cell` is `Send` but not `Sync`.
+        assert_send_sync::<Queue<std::cell::Cell<u32>>>();
+    }
+
     #[test]
     fn no_leaks() {
         static DROP_COUNT: AtomicUsize = AtomicUsize::new(0);
@@ -170,10 +197,8 @@ mod tests {
             q.dequeue(&h);
         }
 
-        // Flush garbage.
-        for _ in 0..10 {
-            let _g = h.pin();
-        }
+        h.pin().flush();
+        c.synchronize();
 
         assert_eq!(DROP_COUNT.load(Ordering::Relaxed), 1000);
     }
@@ -216,7 +241,7 @@ mod tests {
                 let h = c.register();
                 barrier.wait();
                 loop {
-                    if let Some(_) = q.dequeue(&h) {
+                    if q.dequeue(&h).is_some() {
                         let prev = consumed.fetch_add(1, Ordering::Relaxed);
                         if prev + 1 >= total {
                             break;
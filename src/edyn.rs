@@ -0,0 +1,446 @@
+//! Dynamic sorted set on top of static Eytzinger runs.
+//!
+//! Uses the logarithmic method (Bentley-Saxe): inserts land in a small sorted
+//! buffer, a full buffer is frozen into an [`EytzingerTree`] run, and runs of
+//! similar size are merged like the carries of a binary counter, so there are
+//! O(log n) runs and every element is moved O(log n) times in total.
+//!
+//! ```text
+//!  buffer  [ 3 9 ]                       sorted Vec, at most BUFFER_LEN keys
+//!  runs    [ 512 keys ][ 128 ][ 64 ]     Eytzinger runs, sizes decreasing
+//!           live:      1 0 1 ...         one bit per sorted rank, summarised
+//!                                        64 to 1 per level above it
+//! ```
+//!
+//! Removing a key that lives in a run only clears its live bit. Dead keys are
+//! dropped whenever their run takes part in a merge, and the whole set is
+//! compacted into a single run once half of the stored keys are dead. Until
+//! then the summary levels let a lookup skip any stretch of dead keys in
+//! O(log_64 n) word reads.
+
+use crate::{EytzingerTree, eytzinger_to_sorted};
+
+/// Keys held in the insert buffer before it is frozen into a run.
+const BUFFER_LEN: usize = 64;
+
+/// Bitmap of live ranks with a summary level per factor of 64, so the next
+/// live rank is found without walking the dead ones in between.
+struct LiveRanks {
+    /// `levels[0]` has one bit per rank, every bit of `levels[h + 1]` tells
+    /// whether the matching word of `levels[h]` is non-zero. The last level
+    /// is a single word.
+    levels: Vec<Vec<u64>>,
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Words read by [`LiveRanks::next`], for tests that bound lookup cost.
+    static WORDS_READ: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+impl LiveRanks {
+    /// Marks the ranks `0..n` live.
+    fn full(n: usize) -> Self {
+        let mut levels = Vec::new();
+        let mut bits = n;
+        loop {
+            let mut words = vec![u64::MAX; bits.div_ceil(64)];
+            if !bits.is_multiple_of(64) {
+                *words.last_mut().unwrap() = (1 << (bits % 64)) - 1;
+            }
+            bits = words.len();
+            levels.push(words);
+            if bits <= 1 {
+                return Self { levels };
+            }
+        }
+    }
+
+    fn contains(&self, rank: usize) -> bool {
+        self.levels[0][rank / 64] & (1 << (rank % 64)) != 0
+    }
+
+    fn set(&mut self, rank: usize, live: bool) {
+        let mut i = rank;
+        for level in &mut self.levels {
+            let word = &mut level[i / 64];
+            let was_empty = *word == 0;
+            if live {
+                *word |= 1 << (i % 64);
+            } else {
+                *word &= !(1 << (i % 64));
+            }
+            // The summary bit only changes when the word empties or fills.
+            if was_empty == (*word == 0) {
+                return;
+            }
+            i /= 64;
+        }
+    }
+
+    /// Smallest live rank not less than `rank`.
+    fn next(&self, rank: usize) -> Option<usize> {
+        self.next_at(0, rank)
+    }
+
+    /// Smallest set bit of `levels[h]` at or after `i`. Climbs while the rest
+    /// of the word is empty, then descends along the lowest set bits.
+    fn next_at(&self, h: usize, i: usize) -> Option<usize> {
+        #[cfg(test)]
+        WORDS_READ.with(|n| n.set(n.get() + 1));
+
+        let words = &self.levels[h];
+        let w = i / 64;
+        let bits = words.get(w)? & (u64::MAX << (i % 64));
+        if bits != 0 {
+            return Some(w * 64 + bits.trailing_zeros() as usize);
+        }
+        if h + 1 == self.levels.len() {
+            return None;
+        }
+        let w = self.next_at(h + 1, w + 1)?;
+        Some(w * 64 + words[w].trailing_zeros() as usize)
+    }
+}
+
+/// An immutable Eytzinger run with tombstones.
+struct Run<T> {
+    tree: EytzingerTree<T>,
+    /// Keys that are not tombstoned, indexed by sorted rank.
+    live: LiveRanks,
+    /// Number of tombstoned keys.
+    dead: usize,
+}
+
+impl<T: Ord> Run<T> {
+    fn new(sorted: Vec<T>) -> Self {
+        Self {
+            live: LiveRanks::full(sorted.len()),
+            tree: EytzingerTree::new(sorted),
+            dead: 0,
+        }
+    }
+
+    /// Rank of `x` in the run, whether or not it is tombstoned.
+    fn find(&self, x: &T) -> Option<usize> {
+        self.tree.search_rank(x)
+    }
+
+    /// First live key not less than `x`.
+    fn lower_bound(&self, x: &T) -> Option<&T> {
+        let rank = self.live.next(self.tree.lower_bound(x))?;
+        self.tree.get_by_rank(rank)
+    }
+
+    /// Moves the live keys out in sorted order.
+    fn into_sorted(self) -> Vec<T> {
+        let n = self.tree.data.len();
+        let mut slots: Vec<Option<T>> = (0..n).map(|_| None).collect();
+        for (i, x) in self.tree.data.into_iter().enumerate() {
+            let rank = eytzinger_to_sorted(n, i);
+            if self.live.contains(rank) {
+                slots[rank] = Some(x);
+            }
+        }
+        slots.into_iter().flatten().collect()
+    }
+}
+
+/// Merges two sorted vectors with no key in common.
+fn merge<T: Ord>(a: Vec<T>, b: Vec<T>) -> Vec<T> {
+    let mut out = Vec::with_capacity(a.len() + b.len());
+    let mut a = a.into_iter().peekable();
+    let mut b = b.into_iter().peekable();
+
+    loop {
+        let take_a = match (a.peek(), b.peek()) {
+            (Some(x), Some(y)) => x < y,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => return out,
+        };
+        out.extend(if take_a { a.next() } else { b.next() });
+    }
+}
+
+/// Sorted set supporting inserts and removals, see the [module docs](self).
+pub struct DynamicEytzinger<T> {
+    /// Recent inserts, kept sorted.
+    buffer: Vec<T>,
+    /// Frozen runs, largest first. No key is stored in more than one place.
+    runs: Vec<Run<T>>,
+    /// Number of live keys.
+    len: usize,
+    /// Number of tombstoned keys across all runs.
+    dead: usize,
+}
+
+impl<T: Ord> DynamicEytzinger<T> {
+    /// Creates an empty set.
+    pub fn new() -> Self {
+        Self {
+            buffer: Vec::with_capacity(BUFFER_LEN),
+            runs: Vec::new(),
+            len: 0,
+            dead: 0,
+        }
+    }
+
+    /// Inserts `x`, returning `false` if it was already present.
+    ///
+    /// Amortized O(log^2 n): the membership check probes every run, and each
+    /// key takes part in O(log n) merges over its lifetime.
+    pub fn insert(&mut self, x: T) -> bool {
+        if let Err(pos) = self.buffer.binary_search(&x) {
+            for run in &mut self.runs {
+                if let Some(rank) = run.find(&x) {
+                    if run.live.contains(rank) {
+                        return false;
+                    }
+                    // Revive the tombstoned copy instead of storing a second one.
+                    run.live.set(rank, true);
+                    run.dead -= 1;
+                    self.dead -= 1;
+                    self.len += 1;
+                    return true;
+                }
+            }
+
+            self.buffer.insert(pos, x);
+            self.len += 1;
+            if self.buffer.len() == BUFFER_LEN {
+                self.flush();
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Removes `x`, returning `false` if it was not present.
+    pub fn remove(&mut self, x: &T) -> bool {
+        if let Ok(pos) = self.buffer.binary_search(x) {
+            self.buffer.remove(pos);
+            self.len -= 1;
+            return true;
+        }
+
+        for run in &mut self.runs {
+            if let Some(rank) = run.find(x) {
+                if !run.live.contains(rank) {
+                    return false;
+                }
+                run.live.set(rank, false);
+                run.dead += 1;
+                self.dead += 1;
+                self.len -= 1;
+
+                if self.dead > self.len {
+                    self.compact();
+                }
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Returns `true` if the set contains `x`.
+    pub fn contains(&self, x: &T) -> bool {
+        self.buffer.binary_search(x).is_ok()
+            || self
+                .runs
+                .iter()
+                .any(|run| run.find(x).is_some_and(|rank| run.live.contains(rank)))
+    }
+
+    /// Returns the smallest key not less than `x`.
+    pub fn lower_bound(&self, x: &T) -> Option<&T> {
+        let pos = self.buffer.partition_point(|y| y < x);
+        self.runs
+            .iter()
+            .filter_map(|run| run.lower_bound(x))
+            .chain(self.buffer.get(pos))
+            .min()
+    }
+
+    /// Returns the number of keys in the set.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the set holds no keys.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Freezes the buffer into a run, merging it with every smaller-or-equal
+    /// run at the tail so run sizes keep decreasing geometrically.
+    fn flush(&mut self) {
+        let mut sorted = std::mem::replace(&mut self.buffer, Vec::with_capacity(BUFFER_LEN));
+
+        while let Some(run) = self.runs.last() {
+            if run.tree.len() - run.dead > sorted.len() {
+                break;
+            }
+            let run = self.runs.pop().unwrap();
+            self.dead -= run.dead;
+            sorted = merge(run.into_sorted(), sorted);
+        }
+
+        self.runs.push(Run::new(sorted));
+    }
+
+    /// Rebuilds every live key into a single run.
+    fn compact(&mut self) {
+        let mut sorted = std::mem::take(&mut self.buffer);
+        for run in std::mem::take(&mut self.runs) {
+            sorted = merge(run.into_sorted(), sorted);
+        }
+        self.dead = 0;
+        if !sorted.is_empty() {
+            self.runs.push(Run::new(sorted));
+        }
+        self.buffer.reserve(BUFFER_LEN);
+    }
+}
+
+impl<T: Ord> Default for DynamicEytzinger<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+    use rand_chacha::ChaCha8Rng;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn matches_btreeset() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let mut set = DynamicEytzinger::new();
+        let mut model = BTreeSet::new();
+
+        for step in 0..20_000 {
+            let x = rng.random_range(0..2_000u32);
+            match rng.random_range(0..4) {
+                0 | 1 => assert_eq!(set.insert(x), model.insert(x), "insert {x} at {step}"),
+                2 => assert_eq!(set.remove(&x), model.remove(&x), "remove {x} at {step}"),
+                _ => {
+                    assert_eq!(
+                        set.contains(&x),
+                        model.contains(&x),
+                        "contains {x} at {step}"
+                    );
+                    assert_eq!(
+                        set.lower_bound(&x),
+                        model.range(x..).next(),
+                        "lower_bound {x}"
+                    );
+                }
+            }
+            assert_eq!(set.len(), model.len());
+        }
+    }
+
+    #[test]
+    fn reinsert_revives_tombstone() {
+        let mut set = DynamicEytzinger::new();
+        for x in 0..(4 * BUFFER_LEN as u32) {
+            set.insert(x);
+        }
+        assert!(set.buffer.is_empty());
+
+        assert!(set.remove(&5));
+        assert_eq!(set.dead, 1);
+        assert!(!set.contains(&5));
+
+        assert!(set.insert(5));
+        assert_eq!(set.dead, 0);
+        assert!(set.buffer.is_empty());
+        assert!(set.contains(&5));
+    }
+
+    #[test]
+    fn runs_stay_logarithmic() {
+        let mut set = DynamicEytzinger::new();
+        for x in 0..100_000u32 {
+            set.insert(x);
+        }
+        assert_eq!(set.len(), 100_000);
+        assert!(set.runs.len() <= 12, "{} runs", set.runs.len());
+        assert!(
+            set.runs
+                .windows(2)
+                .all(|w| w[0].tree.len() > w[1].tree.len())
+        );
+
+        for x in (0..100_000).step_by(7) {
+            assert!(set.contains(&x));
+        }
+    }
+
+    #[test]
+    fn live_ranks_next() {
+        let n = 64 * 64 * 3 + 17;
+        let mut live = LiveRanks::full(n);
+        assert_eq!(live.levels.len(), 3);
+        let mut model = vec![true; n];
+        for rank in (0..n).filter(|r| r % 5 != 0 || *r < 64 * 70) {
+            live.set(rank, false);
+            model[rank] = false;
+        }
+        live.set(n - 1, true);
+        model[n - 1] = true;
+
+        for rank in 0..=n {
+            let expected = (rank..n).find(|&r| model[r]);
+            assert_eq!(live.next(rank), expected, "next({rank})");
+        }
+    }
+
+    #[test]
+    fn lower_bound_skips_dead_keys_cheaply() {
+        const N: u32 = 1 << 20;
+        let mut set = DynamicEytzinger::new();
+        for x in 0..N {
+            set.insert(x);
+        }
+        // Remove the smallest 40%, not enough to trigger compaction.
+        let removed = N / 5 * 2;
+        for x in 0..removed {
+            assert!(set.remove(&x));
+        }
+        assert_eq!(set.dead, removed as usize);
+
+        WORDS_READ.with(|n| n.set(0));
+        assert_eq!(set.lower_bound(&0), Some(&removed));
+        let words = WORDS_READ.with(|n| n.get());
+
+        // Every run climbs and descends its summary levels at most once.
+        let bound: usize = set.runs.iter().map(|r| 2 * r.live.levels.len()).sum();
+        assert!(words <= bound, "{words} words read, bound {bound}");
+    }
+
+    #[test]
+    fn removals_compact() {
+        let mut set = DynamicEytzinger::new();
+        for x in 0..1_000u32 {
+            set.insert(x);
+        }
+        for x in 0..900u32 {
+            assert!(set.remove(&x));
+            assert!(!set.remove(&x));
+        }
+        assert_eq!(set.len(), 100);
+        assert!(set.dead <= set.len);
+        assert_eq!(set.lower_bound(&0), Some(&900));
+
+        // Present keys are rejected, removed ones can come back.
+        assert!(!set.insert(950));
+        assert!(set.insert(10));
+        assert_eq!(set.lower_bound(&0), Some(&10));
+    }
+}
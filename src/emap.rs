@@ -0,0 +1,201 @@
+//! Sorted map over an Eytzinger layout.
+//!
+//! Keys and values are permuted into the same Eytzinger order but kept in
+//! separate arrays, so a search only ever touches the densely packed keys and
+//! reads a single value once the position is known.
+
+use crate::{eytzinger_layout, eytzinger_to_sorted, lower_bound_index, sorted_to_eytzinger};
+
+pub struct EytzingerMap<K, V> {
+    keys: Vec<K>,
+    values: Vec<V>,
+}
+
+impl<K: Ord, V> EytzingerMap<K, V> {
+    /// Builds a map from `(key, value)` pairs sorted by key. With duplicate keys
+    /// lookups resolve to the first pair in sorted order.
+    pub fn new(sorted: Vec<(K, V)>) -> Self {
+        debug_assert!(
+            sorted.windows(2).all(|w| w[0].0 <= w[1].0),
+            "input must be sorted by key"
+        );
+        let n = sorted.len();
+        let (keys, values): (Vec<K>, Vec<V>) = sorted.into_iter().unzip();
+        Self {
+            keys: eytzinger_layout(keys.into_iter(), n),
+            values: eytzinger_layout(values.into_iter(), n),
+        }
+    }
+
+    /// Returns the value associated with `key`.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let i = lower_bound_index(&self.keys, key);
+        if i < self.keys.len() && self.keys[i] == *key {
+            Some(&self.values[i])
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if the map contains `key`.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns the first entry whose key is not less than `key`.
+    pub fn get_lower_bound(&self, key: &K) -> Option<(&K, &V)> {
+        let i = lower_bound_index(&self.keys, key);
+        if i < self.keys.len() {
+            Some((&self.keys[i], &self.values[i]))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the rank in sorted order of the first key not less than `key`,
+    /// or `len` if there is none.
+    pub fn lower_bound(&self, key: &K) -> usize {
+        let n = self.keys.len();
+        match lower_bound_index(&self.keys, key) {
+            i if i < n => eytzinger_to_sorted(n, i),
+            _ => n,
+        }
+    }
+}
+
+impl<K, V> EytzingerMap<K, V> {
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns `true` if the map holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Returns the entry of rank `rank` in sorted order.
+    pub fn get_by_rank(&self, rank: usize) -> Option<(&K, &V)> {
+        if rank < self.keys.len() {
+            let i = sorted_to_eytzinger(self.keys.len(), rank);
+            Some((&self.keys[i], &self.values[i]))
+        } else {
+            None
+        }
+    }
+
+    /// Iterates over the entries in sorted key order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            map: self,
+            front: 0,
+            back: self.keys.len(),
+        }
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a EytzingerMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Sorted-order iterator over the entries of an [`EytzingerMap`].
+pub struct Iter<'a, K, V> {
+    map: &'a EytzingerMap<K, V>,
+    /// Next rank to yield from the front.
+    front: usize,
+    /// One past the next rank to yield from the back.
+    back: usize,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.front == self.back {
+            return None;
+        }
+        let entry = self.map.get_by_rank(self.front);
+        self.front += 1;
+        entry
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.back - self.front;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Iter<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        self.map.get_by_rank(self.back)
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_finds_every_key() {
+        for n in 0..100u32 {
+            let pairs: Vec<(u32, String)> = (0..n).map(|k| (k * 2, format!("v{k}"))).collect();
+            let map = EytzingerMap::new(pairs);
+            assert_eq!(map.len(), n as usize);
+
+            for k in 0..n {
+                assert_eq!(map.get(&(k * 2)), Some(&format!("v{k}")));
+                assert_eq!(map.get(&(k * 2 + 1)), None);
+            }
+        }
+    }
+
+    #[test]
+    fn get_lower_bound() {
+        let map = EytzingerMap::new(vec![(10, 'a'), (20, 'b'), (30, 'c')]);
+
+        assert_eq!(map.get_lower_bound(&0), Some((&10, &'a')));
+        assert_eq!(map.get_lower_bound(&10), Some((&10, &'a')));
+        assert_eq!(map.get_lower_bound(&11), Some((&20, &'b')));
+        assert_eq!(map.get_lower_bound(&30), Some((&30, &'c')));
+        assert_eq!(map.get_lower_bound(&31), None);
+
+        assert_eq!(map.lower_bound(&11), 1);
+        assert_eq!(map.lower_bound(&31), 3);
+    }
+
+    #[test]
+    fn iter_is_sorted() {
+        let pairs: Vec<(u32, u32)> = (0..37).map(|k| (k, k * 10)).collect();
+        let map = EytzingerMap::new(pairs.clone());
+
+        let forward: Vec<(u32, u32)> = map.iter().map(|(&k, &v)| (k, v)).collect();
+        assert_eq!(forward, pairs);
+
+        let backward: Vec<u32> = map.iter().rev().map(|(&k, _)| k).collect();
+        assert_eq!(backward, (0..37).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn values_are_dropped() {
+        use std::rc::Rc;
+
+        let tracker = Rc::new(());
+        let pairs: Vec<(u32, Rc<()>)> = (0..10).map(|k| (k, tracker.clone())).collect();
+        let map = EytzingerMap::new(pairs);
+        assert_eq!(Rc::strong_count(&tracker), 11);
+
+        drop(map);
+        assert_eq!(Rc::strong_count(&tracker), 1);
+    }
+}
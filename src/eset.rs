@@ -0,0 +1,290 @@
+//! Sorted set with set-algebra operations over an Eytzinger layout.
+//!
+//! Membership tests go through the [`EytzingerTree`] layout, while operations
+//! that combine two sets walk one of them in sorted order and either probe or
+//! gallop through the other.
+
+use crate::EytzingerTree;
+
+/// Set of unique keys stored in Eytzinger order.
+pub struct EytzingerSet<T> {
+    tree: EytzingerTree<T>,
+}
+
+impl<T: Ord> EytzingerSet<T> {
+    /// Builds the set from a sorted vector, dropping duplicates.
+    pub fn new(mut sorted: Vec<T>) -> Self {
+        sorted.dedup();
+        Self {
+            tree: EytzingerTree::new(sorted),
+        }
+    }
+
+    /// Returns `true` if the set contains `x`.
+    pub fn contains(&self, x: &T) -> bool {
+        self.tree.search(x).is_some()
+    }
+
+    /// Returns the underlying tree, for rank and range queries.
+    pub fn as_tree(&self) -> &EytzingerTree<T> {
+        &self.tree
+    }
+
+    /// Iterates over the keys in sorted order.
+    pub fn iter(&self) -> crate::Iter<'_, T> {
+        self.tree.iter()
+    }
+
+    /// Returns the number of keys in the set.
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// Returns `true` if the set holds no keys.
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+}
+
+impl<T: Ord + Clone> EytzingerSet<T> {
+    /// Returns the keys present in both sets.
+    ///
+    /// Walks the smaller set and probes the larger one, which costs
+    /// O(m log n) for sets of sizes `m <= n`.
+    pub fn intersect(&self, other: &Self) -> Self {
+        let (small, large) = if self.len() <= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let sorted = small
+            .iter()
+            .filter(|x| large.contains(x))
+            .cloned()
+            .collect();
+        Self::from_sorted_unique(sorted)
+    }
+
+    /// Returns the keys present in either set.
+    pub fn union(&self, other: &Self) -> Self {
+        let mut sorted = Vec::with_capacity(self.len() + other.len());
+        let mut a = self.iter().peekable();
+        let mut b = other.iter().peekable();
+
+        loop {
+            let next = match (a.peek(), b.peek()) {
+                (Some(x), Some(y)) if x < y => a.next(),
+                (Some(x), Some(y)) if y < x => b.next(),
+                (Some(_), Some(_)) => {
+                    b.next();
+                    a.next()
+                }
+                (Some(_), None) => a.next(),
+                (None, Some(_)) => b.next(),
+                (None, None) => break,
+            };
+            sorted.extend(next.cloned());
+        }
+        Self::from_sorted_unique(sorted)
+    }
+
+    /// Returns the keys of `self` that are not in `other`.
+    pub fn difference(&self, other: &Self) -> Self {
+        let sorted = self
+            .iter()
+            .filter(|x| !other.contains(x))
+            .cloned()
+            .collect();
+        Self::from_sorted_unique(sorted)
+    }
+
+    /// Returns the keys present in both sets using galloping (exponential)
+    /// search over the larger set's ranks.
+    ///
+    /// Costs O(m log(n / m)), which beats [`intersect`](Self::intersect) when
+    /// the sets have similar sizes or their keys cluster.
+    pub fn gallop_intersect(&self, other: &Self) -> Self {
+        let (small, large) = if self.len() <= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let sorted = gallop_intersect(small.iter(), large.len(), |r| {
+            large.tree.get_by_rank(r).unwrap()
+        });
+        Self::from_sorted_unique(sorted)
+    }
+
+    /// Returns the keys of the set that also appear in the sorted slice
+    /// `other`, galloping through whichever side is larger.
+    pub fn gallop_intersect_slice(&self, other: &[T]) -> Self {
+        debug_assert!(other.is_sorted(), "slice must be sorted");
+        let sorted = if self.len() <= other.len() {
+            gallop_intersect(self.iter(), other.len(), |r| &other[r])
+        } else {
+            gallop_intersect(other.iter(), self.len(), |r| {
+                self.tree.get_by_rank(r).unwrap()
+            })
+        };
+        Self::from_sorted_unique(sorted)
+    }
+
+    fn from_sorted_unique(sorted: Vec<T>) -> Self {
+        debug_assert!(sorted.windows(2).all(|w| w[0] < w[1]));
+        Self {
+            tree: EytzingerTree::new(sorted),
+        }
+    }
+}
+
+impl<T: Ord> FromIterator<T> for EytzingerSet<T> {
+    /// Collects keys in any order, sorting and deduplicating them.
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut keys: Vec<T> = iter.into_iter().collect();
+        keys.sort_unstable();
+        Self::new(keys)
+    }
+}
+
+impl<'a, T: Ord> IntoIterator for &'a EytzingerSet<T> {
+    type Item = &'a T;
+    type IntoIter = crate::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Intersects the sorted keys of `small` with the `len` sorted keys returned by
+/// `get`, galloping forward from the previous match for every key of `small`.
+fn gallop_intersect<'a, T, I, F>(small: I, len: usize, get: F) -> Vec<T>
+where
+    T: Ord + Clone + 'a,
+    I: Iterator<Item = &'a T>,
+    F: Fn(usize) -> &'a T,
+{
+    let mut out = Vec::new();
+    let mut lo = 0;
+
+    for x in small {
+        lo = gallop(&get, lo, len, x);
+        if lo == len {
+            break;
+        }
+        if get(lo) == x {
+            out.push(x.clone());
+            lo += 1;
+        }
+    }
+    out
+}
+
+/// Returns the first index in `lo..len` whose key is not less than `x`, or
+/// `len`. Doubles the step until it overshoots, then binary searches the last
+/// gap, so the cost is logarithmic in the distance travelled.
+fn gallop<'a, T: Ord + 'a>(
+    get: impl Fn(usize) -> &'a T,
+    mut lo: usize,
+    len: usize,
+    x: &T,
+) -> usize {
+    let mut step = 1;
+    let mut hi = lo;
+    while hi < len && get(hi) < x {
+        lo = hi + 1;
+        hi = lo.saturating_add(step);
+        step *= 2;
+    }
+
+    let mut hi = hi.min(len);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if get(mid) < x {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    lo
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn set_of(keys: &[u32]) -> EytzingerSet<u32> {
+        keys.iter().copied().collect()
+    }
+
+    fn keys_of(set: &EytzingerSet<u32>) -> Vec<u32> {
+        set.iter().copied().collect()
+    }
+
+    #[test]
+    fn dedups_and_contains() {
+        let set = set_of(&[5, 1, 3, 5, 1, 9]);
+        assert_eq!(keys_of(&set), vec![1, 3, 5, 9]);
+        assert!(set.contains(&3));
+        assert!(!set.contains(&4));
+        assert_eq!(set.len(), 4);
+    }
+
+    #[test]
+    fn gallop_finds_lower_bound() {
+        let keys: Vec<u32> = (0..100).map(|x| x * 2).collect();
+        for lo in [0, 1, 10, 99, 100] {
+            for x in 0..205 {
+                let expected = lo.max(keys.partition_point(|&k| k < x));
+                assert_eq!(gallop(|i| &keys[i], lo, keys.len(), &x), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn algebra_matches_btreeset() {
+        let cases: [(Vec<u32>, Vec<u32>); 5] = [
+            (vec![], vec![1, 2, 3]),
+            ((0..100).collect(), (50..150).collect()),
+            (
+                (0..1000).step_by(3).collect(),
+                (0..1000).step_by(5).collect(),
+            ),
+            (vec![7], (0..10_000).collect()),
+            (
+                (0..64).map(|x| x * x).collect(),
+                (0..4096).step_by(7).collect(),
+            ),
+        ];
+
+        for (a, b) in cases {
+            let (sa, sb) = (set_of(&a), set_of(&b));
+            let (ma, mb): (BTreeSet<u32>, BTreeSet<u32>) =
+                (a.iter().copied().collect(), b.iter().copied().collect());
+
+            let intersection: Vec<u32> = ma.intersection(&mb).copied().collect();
+            assert_eq!(keys_of(&sa.intersect(&sb)), intersection);
+            assert_eq!(keys_of(&sb.intersect(&sa)), intersection);
+            assert_eq!(keys_of(&sa.gallop_intersect(&sb)), intersection);
+            assert_eq!(keys_of(&sb.gallop_intersect(&sa)), intersection);
+            assert_eq!(keys_of(&sa.gallop_intersect_slice(&b)), intersection);
+            assert_eq!(keys_of(&sb.gallop_intersect_slice(&a)), intersection);
+
+            let union: Vec<u32> = ma.union(&mb).copied().collect();
+            assert_eq!(keys_of(&sa.union(&sb)), union);
+
+            let difference: Vec<u32> = ma.difference(&mb).copied().collect();
+            assert_eq!(keys_of(&sa.difference(&sb)), difference);
+        }
+    }
+
+    #[test]
+    fn gallop_intersect_slice_with_duplicates() {
+        let set = set_of(&(0..100).collect::<Vec<_>>());
+        let slice = [3, 3, 3, 50, 50, 99, 99, 200];
+        assert_eq!(
+            keys_of(&set.gallop_intersect_slice(&slice)),
+            vec![3, 50, 99]
+        );
+    }
+}
@@ -0,0 +1,421 @@
+//! On-disk format and zero-copy view for Eytzinger indexes.
+//!
+//! An [`EytzingerTree`] over plain-old-data keys can be written once with
+//! [`EytzingerTree::write_to`] and searched in place from any byte buffer,
+//! typically a memory-mapped file, through an [`EytzingerView`].
+//!
+//! ```text
+//!  offset  size  field
+//!  0       8     magic      b"ISLDEYTZ"
+//!  8       2     version    little-endian u16, currently 1
+//!  10      1     endianness 0 = little, 1 = big, byte order of the elements
+//!  11      1     width      size of one element in bytes, at most 255
+//!  12      4     reserved   zero
+//!  16      8     len        little-endian u64, number of elements
+//!  24      8     checksum   little-endian u64, FNV-1a of the element bytes
+//!  32      ..    elements   `len * width` bytes in Eytzinger order
+//! ```
+//!
+//! The elements start 32 bytes in, so a buffer aligned to the element type
+//! (a page-aligned mapping always is) can be reinterpreted without copying.
+
+use std::{fmt, io, mem};
+
+use crate::{EytzingerTree, eytzinger_to_sorted, partition_index, sorted_to_eytzinger};
+
+const MAGIC: [u8; 8] = *b"ISLDEYTZ";
+const VERSION: u16 = 1;
+const HEADER_LEN: usize = 32;
+
+const LITTLE_ENDIAN: u8 = 0;
+const BIG_ENDIAN: u8 = 1;
+
+const NATIVE_ENDIAN: u8 = if cfg!(target_endian = "little") {
+    LITTLE_ENDIAN
+} else {
+    BIG_ENDIAN
+};
+
+/// Key types that can be stored in the on-disk format and viewed in place.
+///
+/// # Safety
+///
+/// Implementors must have no padding bytes and every bit pattern of
+/// `size_of::<Self>()` bytes must be a valid value.
+pub unsafe trait Pod: Copy + Ord + 'static {}
+
+unsafe impl Pod for u8 {}
+unsafe impl Pod for u16 {}
+unsafe impl Pod for u32 {}
+unsafe impl Pod for u64 {}
+unsafe impl Pod for u128 {}
+unsafe impl Pod for usize {}
+unsafe impl Pod for i8 {}
+unsafe impl Pod for i16 {}
+unsafe impl Pod for i32 {}
+unsafe impl Pod for i64 {}
+unsafe impl Pod for i128 {}
+unsafe impl Pod for isize {}
+
+/// Reasons a byte buffer is rejected by [`EytzingerView::new`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatError {
+    /// The buffer is shorter than the header or the elements it announces.
+    Truncated,
+    /// The buffer does not start with the expected magic bytes.
+    Magic,
+    /// The format version is not supported.
+    Version(u16),
+    /// The elements were written on a machine with a different byte order.
+    Endianness,
+    /// The element width does not match the requested key type.
+    Width { expected: usize, found: usize },
+    /// The elements are not aligned for the requested key type.
+    Alignment,
+    /// The element bytes do not match the stored checksum.
+    Checksum,
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "buffer is truncated"),
+            Self::Magic => write!(f, "not an Eytzinger index"),
+            Self::Version(v) => write!(f, "unsupported format version {v}"),
+            Self::Endianness => write!(f, "index was written with a different byte order"),
+            Self::Width { expected, found } => {
+                write!(f, "element width is {found} bytes, expected {expected}")
+            }
+            Self::Alignment => write!(f, "elements are misaligned for the key type"),
+            Self::Checksum => write!(f, "checksum mismatch"),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+/// 64-bit FNV-1a.
+fn checksum(bytes: &[u8]) -> u64 {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    bytes
+        .iter()
+        .fold(OFFSET, |hash, &b| (hash ^ b as u64).wrapping_mul(PRIME))
+}
+
+/// Views a slice of plain-old-data as its raw bytes.
+fn as_bytes<T: Pod>(data: &[T]) -> &[u8] {
+    // SAFETY: `Pod` types have no padding, so every byte is initialized.
+    unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, mem::size_of_val(data)) }
+}
+
+impl<T: Pod> EytzingerTree<T> {
+    /// Writes the tree in the on-disk format described in the [module
+    /// docs](crate::eview).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` is wider than 255 bytes, which the width field cannot
+    /// record.
+    pub fn write_to<W: io::Write>(&self, mut w: W) -> io::Result<()> {
+        let width = mem::size_of::<T>();
+        assert!(
+            width <= u8::MAX as usize,
+            "element width {width} does not fit the format's width field"
+        );
+        let bytes = as_bytes(&self.data);
+
+        let mut header = [0u8; HEADER_LEN];
+        header[0..8].copy_from_slice(&MAGIC);
+        header[8..10].copy_from_slice(&VERSION.to_le_bytes());
+        header[10] = NATIVE_ENDIAN;
+        header[11] = width as u8;
+        header[16..24].copy_from_slice(&(self.data.len() as u64).to_le_bytes());
+        header[24..32].copy_from_slice(&checksum(bytes).to_le_bytes());
+
+        w.write_all(&header)?;
+        w.write_all(bytes)
+    }
+}
+
+/// Read-only Eytzinger index borrowed from a byte buffer.
+///
+/// Offers the same searches as [`EytzingerTree`]; positions refer to the
+/// Eytzinger order and ranks to the original sorted order.
+#[derive(Clone, Copy)]
+pub struct EytzingerView<'a, T> {
+    data: &'a [T],
+}
+
+impl<'a, T: Pod> EytzingerView<'a, T> {
+    /// Validates the header and checksum of `bytes` and borrows its elements.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, FormatError> {
+        let (data, expected) = Self::parse(bytes)?;
+        if checksum(as_bytes(data)) != expected {
+            return Err(FormatError::Checksum);
+        }
+        Ok(Self { data })
+    }
+
+    /// Like [`new`](Self::new) but skips the checksum, which touches every
+    /// page of the index. The header is still validated.
+    pub fn new_unverified(bytes: &'a [u8]) -> Result<Self, FormatError> {
+        let (data, _) = Self::parse(bytes)?;
+        Ok(Self { data })
+    }
+
+    /// Validates the header and returns the elements with the stored checksum.
+    fn parse(bytes: &'a [u8]) -> Result<(&'a [T], u64), FormatError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(FormatError::Truncated);
+        }
+        let (header, body) = bytes.split_at(HEADER_LEN);
+        let u16_at = |i: usize| u16::from_le_bytes(header[i..i + 2].try_into().unwrap());
+        let u64_at = |i: usize| u64::from_le_bytes(header[i..i + 8].try_into().unwrap());
+
+        if header[0..8] != MAGIC {
+            return Err(FormatError::Magic);
+        }
+        if u16_at(8) != VERSION {
+            return Err(FormatError::Version(u16_at(8)));
+        }
+        if header[10] != NATIVE_ENDIAN {
+            return Err(FormatError::Endianness);
+        }
+        let width = mem::size_of::<T>();
+        if header[11] as usize != width {
+            return Err(FormatError::Width {
+                expected: width,
+                found: header[11] as usize,
+            });
+        }
+
+        let len = usize::try_from(u64_at(16)).map_err(|_| FormatError::Truncated)?;
+        let size = len.checked_mul(width).ok_or(FormatError::Truncated)?;
+        if body.len() < size {
+            return Err(FormatError::Truncated);
+        }
+        if body.as_ptr().align_offset(mem::align_of::<T>()) != 0 {
+            return Err(FormatError::Alignment);
+        }
+
+        // SAFETY: the range is in bounds and aligned, and `Pod` accepts any bit
+        // pattern.
+        let data = unsafe { std::slice::from_raw_parts(body.as_ptr() as *const T, len) };
+        Ok((data, u64_at(24)))
+    }
+
+    /// Returns the position in the Eytzinger order of the first occurrence of
+    /// `target` in sorted order.
+    ///
+    /// With duplicates this can be a different copy than the one
+    /// [`EytzingerTree::search`] stops at.
+    pub fn search(&self, target: &T) -> Option<usize> {
+        let i = partition_index(self.data, |x| x < target);
+        if i < self.data.len() && self.data[i] == *target {
+            Some(i)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the rank of `target` in sorted order. With duplicates the rank
+    /// of the first occurrence is returned.
+    pub fn search_rank(&self, target: &T) -> Option<usize> {
+        self.search(target)
+            .map(|i| eytzinger_to_sorted(self.data.len(), i))
+    }
+
+    /// Returns the rank of the first element that is not less than `target`.
+    pub fn lower_bound(&self, target: &T) -> usize {
+        self.partition_point(|x| x < target)
+    }
+
+    /// Returns the rank of the first element that is greater than `target`.
+    pub fn upper_bound(&self, target: &T) -> usize {
+        self.partition_point(|x| x <= target)
+    }
+
+    /// Returns the rank of the first element for which `pred` is false.
+    pub fn partition_point<P>(&self, pred: P) -> usize
+    where
+        P: FnMut(&T) -> bool,
+    {
+        let n = self.data.len();
+        match partition_index(self.data, pred) {
+            i if i < n => eytzinger_to_sorted(n, i),
+            _ => n,
+        }
+    }
+
+    /// Returns the element of rank `rank` in sorted order.
+    pub fn get_by_rank(&self, rank: usize) -> Option<&'a T> {
+        if rank < self.data.len() {
+            Some(&self.data[sorted_to_eytzinger(self.data.len(), rank)])
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number of elements in the index.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns `true` if the index holds no elements.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Returns the elements in Eytzinger order.
+    pub fn as_slice(&self) -> &'a [T] {
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Copies `bytes` into a buffer aligned like an mmap would be.
+    fn aligned(bytes: &[u8]) -> Vec<u128> {
+        let mut buf = vec![0u128; bytes.len().div_ceil(16)];
+        // SAFETY: the destination holds at least `bytes.len()` bytes.
+        unsafe {
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), buf.as_mut_ptr() as *mut u8, bytes.len())
+        };
+        buf
+    }
+
+    fn bytes_of(buf: &[u128], len: usize) -> &[u8] {
+        &as_bytes(buf)[..len]
+    }
+
+    fn serialize<T: Pod>(tree: &EytzingerTree<T>) -> Vec<u8> {
+        let mut out = Vec::new();
+        tree.write_to(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn round_trip() {
+        for n in [0u64, 1, 2, 7, 100, 1000] {
+            let sorted: Vec<u64> = (0..n).map(|x| x * 3).collect();
+            let tree = EytzingerTree::new(sorted.clone());
+            let bytes = serialize(&tree);
+            assert_eq!(bytes.len(), HEADER_LEN + 8 * n as usize);
+
+            let buf = aligned(&bytes);
+            let view = EytzingerView::<u64>::new(bytes_of(&buf, bytes.len())).unwrap();
+            assert_eq!(view.len(), tree.len());
+            assert_eq!(view.as_slice(), tree.as_ref());
+
+            for x in 0..3 * n + 2 {
+                assert_eq!(view.search(&x), tree.search(&x));
+                assert_eq!(view.search_rank(&x), tree.search_rank(&x));
+                assert_eq!(view.lower_bound(&x), tree.lower_bound(&x));
+                assert_eq!(view.upper_bound(&x), tree.upper_bound(&x));
+            }
+            for (rank, x) in sorted.iter().enumerate() {
+                assert_eq!(view.get_by_rank(rank), Some(x));
+            }
+        }
+    }
+
+    #[test]
+    fn duplicates_resolve_to_first_occurrence() {
+        let tree = EytzingerTree::new(vec![1u32, 3, 3, 3, 3, 3, 7, 9, 9, 12]);
+        let bytes = serialize(&tree);
+        let buf = aligned(&bytes);
+        let view = EytzingerView::<u32>::new(bytes_of(&buf, bytes.len())).unwrap();
+
+        for x in 0..14 {
+            let first = tree
+                .search_rank(&x)
+                .map(|rank| tree.sorted_to_eytzinger(rank));
+            assert_eq!(view.search(&x), first, "x = {x}");
+            assert_eq!(view.search_rank(&x), tree.search_rank(&x));
+        }
+        assert_ne!(view.search(&3), tree.search(&3));
+    }
+
+    #[test]
+    #[should_panic(expected = "element width 256")]
+    fn rejects_wide_elements() {
+        #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+        struct Wide([u8; 256]);
+        unsafe impl Pod for Wide {}
+
+        serialize(&EytzingerTree::new(vec![Wide([0; 256])]));
+    }
+
+    #[test]
+    fn header_layout() {
+        let bytes = serialize(&EytzingerTree::new(vec![1i32, 2, 3]));
+        assert_eq!(&bytes[0..8], b"ISLDEYTZ");
+        assert_eq!(u16::from_le_bytes([bytes[8], bytes[9]]), 1);
+        assert_eq!(bytes[10], NATIVE_ENDIAN);
+        assert_eq!(bytes[11], 4);
+        assert_eq!(u64::from_le_bytes(bytes[16..24].try_into().unwrap()), 3);
+    }
+
+    #[test]
+    fn rejects_corruption() {
+        let bytes = serialize(&EytzingerTree::new((0..64u32).collect()));
+        let check = |bytes: &[u8]| {
+            let buf = aligned(bytes);
+            EytzingerView::<u32>::new(bytes_of(&buf, bytes.len())).map(|_| ())
+        };
+        assert_eq!(check(&bytes), Ok(()));
+
+        assert_eq!(check(&bytes[..HEADER_LEN - 1]), Err(FormatError::Truncated));
+        assert_eq!(
+            check(&bytes[..bytes.len() - 1]),
+            Err(FormatError::Truncated)
+        );
+
+        let mut bad = bytes.clone();
+        bad[0] = b'X';
+        assert_eq!(check(&bad), Err(FormatError::Magic));
+
+        let mut bad = bytes.clone();
+        bad[8] = 2;
+        assert_eq!(check(&bad), Err(FormatError::Version(2)));
+
+        let mut bad = bytes.clone();
+        bad[10] ^= 1;
+        assert_eq!(check(&bad), Err(FormatError::Endianness));
+
+        let mut bad = bytes.clone();
+        *bad.last_mut().unwrap() ^= 0xff;
+        assert_eq!(check(&bad), Err(FormatError::Checksum));
+
+        // Skipping verification accepts the corrupted payload.
+        let buf = aligned(&bad);
+        assert!(EytzingerView::<u32>::new_unverified(bytes_of(&buf, bad.len())).is_ok());
+    }
+
+    #[test]
+    fn rejects_wrong_width_and_alignment() {
+        let bytes = serialize(&EytzingerTree::new((0..16u32).collect()));
+        let buf = aligned(&bytes);
+
+        assert_eq!(
+            EytzingerView::<u64>::new(bytes_of(&buf, bytes.len())).err(),
+            Some(FormatError::Width {
+                expected: 8,
+                found: 4
+            })
+        );
+
+        // Shift the buffer by one byte so the elements are misaligned.
+        let mut shifted = vec![0u8];
+        shifted.extend_from_slice(&bytes);
+        let buf = aligned(&shifted);
+        assert_eq!(
+            EytzingerView::<u32>::new(&bytes_of(&buf, shifted.len())[1..]).err(),
+            Some(FormatError::Alignment)
+        );
+    }
+}
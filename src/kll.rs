@@ -0,0 +1,344 @@
+//! Mergeable streaming quantile sketch (KLL).
+//!
+//! Karnin, Lang and Liberty, "Optimal Quantile Approximation in Streams"
+//! (FOCS 2016). The sketch keeps a stack of compactors: level `h` holds items
+//! that each stand for `2^h` inserted items. When a level overflows it is
+//! sorted and every other item, starting at a random offset, is promoted to
+//! the level above while the rest are dropped, so total weight is preserved
+//! and ranks shift by at most `2^h` per compaction.
+//!
+//! ```text
+//!  level 2  (w=4)  [ 17 40 ]                     capacity k (2/3)^0
+//!  level 1  (w=2)  [ 3 9 25 51 ]                 capacity k (2/3)^1
+//!  level 0  (w=1)  [ 8 1 33 12 5 ... ]           capacity k (2/3)^2
+//! ```
+//!
+//! Capacities shrink geometrically towards the bottom, so the sketch retains
+//! O(k) items regardless of stream length. With the default `k = 200` the
+//! rank of any answer is within 1.65% of the stream length of the exact rank
+//! with 99% probability.
+
+use std::hash::{BuildHasher, Hasher, RandomState};
+
+/// Accuracy parameter used by [`KllSketch::default`].
+pub const DEFAULT_K: usize = 200;
+
+/// Smallest capacity any level is given.
+const MIN_CAPACITY: usize = 2;
+
+/// Streaming quantile sketch, see the [module docs](self).
+pub struct KllSketch<T> {
+    /// Accuracy parameter, the capacity of the top level.
+    k: usize,
+    /// Compactors, level `h` holds items of weight `2^h`.
+    levels: Vec<Vec<T>>,
+    /// Number of items inserted, including those merged in.
+    n: usize,
+    /// Items currently held across all levels.
+    retained: usize,
+    /// Xorshift state for compaction offsets.
+    rng: u64,
+}
+
+impl<T: Ord + Clone> KllSketch<T> {
+    /// Creates an empty sketch with accuracy parameter `k`.
+    ///
+    /// Rank error shrinks as roughly `1.65 / k` and space grows as `3k`. Each sketch seeds its
+    /// compaction coin flips independently, so sketches built on different shards make
+    /// uncorrelated errors when merged.
+    pub fn new(k: usize) -> Self {
+        Self::with_seed(k, RandomState::new().build_hasher().finish())
+    }
+
+    /// Like [`new`](Self::new), but with a fixed seed for the compaction coin flips so the sketch
+    /// is reproducible. Sketches that will be merged should use different seeds.
+    pub fn with_seed(k: usize, seed: u64) -> Self {
+        assert!(k >= MIN_CAPACITY, "k must be at least {MIN_CAPACITY}");
+        Self {
+            k,
+            levels: vec![Vec::new()],
+            n: 0,
+            retained: 0,
+            rng: splitmix64(seed).max(1),
+        }
+    }
+
+    /// Adds `x` to the stream.
+    pub fn insert(&mut self, x: T) {
+        self.levels[0].push(x);
+        self.n += 1;
+        self.retained += 1;
+        if self.retained >= self.max_retained() {
+            self.compress();
+        }
+    }
+
+    /// Folds `other` into `self`, as if every item inserted into `other` had
+    /// been inserted into `self`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sketches were built with different `k`.
+    pub fn merge(&mut self, other: Self) {
+        assert_eq!(self.k, other.k, "cannot merge sketches with different k");
+        if other.levels.len() > self.levels.len() {
+            self.levels.resize_with(other.levels.len(), Vec::new);
+        }
+        for (level, items) in self.levels.iter_mut().zip(other.levels) {
+            level.extend(items);
+        }
+        self.n += other.n;
+        self.retained += other.retained;
+        while self.retained >= self.max_retained() {
+            self.compress();
+        }
+    }
+
+    /// Returns an item whose rank in the stream is approximately `q * (n - 1)`,
+    /// the rank [`select_nth`](crate::select::select_nth) picks for the
+    /// `q`-quantile, or `None` if the sketch is empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `q` is not within `0.0..=1.0`.
+    pub fn quantile(&self, q: f64) -> Option<T> {
+        assert!((0.0..=1.0).contains(&q), "quantile {q} outside 0..=1");
+        if self.n == 0 {
+            return None;
+        }
+
+        let target = (q * (self.n - 1) as f64) as usize;
+        let mut cumulative = 0;
+        self.weighted_items()
+            .into_iter()
+            .find(|&(_, weight)| {
+                cumulative += weight;
+                cumulative > target
+            })
+            .map(|(x, _)| x.clone())
+    }
+
+    /// Returns the approximate number of inserted items not greater than `x`.
+    pub fn rank(&self, x: &T) -> usize {
+        self.levels
+            .iter()
+            .enumerate()
+            .map(|(h, items)| (items.iter().filter(|y| *y <= x).count()) << h)
+            .sum()
+    }
+
+    /// Returns the number of items inserted.
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    /// Returns `true` if nothing has been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /// Returns the number of items the sketch currently stores.
+    pub fn retained(&self) -> usize {
+        self.retained
+    }
+
+    /// Capacity of level `h`, `k (2/3)^d` where `d` is the depth below the top.
+    fn capacity(&self, h: usize) -> usize {
+        let depth = (self.levels.len() - 1 - h) as i32;
+        let capacity = (self.k as f64 * (2.0f64 / 3.0).powi(depth)).ceil() as usize;
+        capacity.max(MIN_CAPACITY)
+    }
+
+    fn max_retained(&self) -> usize {
+        (0..self.levels.len()).map(|h| self.capacity(h)).sum()
+    }
+
+    /// Compacts the lowest level that is at capacity into the one above.
+    fn compress(&mut self) {
+        for h in 0..self.levels.len() {
+            if self.levels[h].len() < self.capacity(h) {
+                continue;
+            }
+            if h + 1 == self.levels.len() {
+                self.levels.push(Vec::new());
+            }
+
+            let mut items = std::mem::take(&mut self.levels[h]);
+            items.sort_unstable();
+            // An odd item out stays behind so that only whole pairs are compacted.
+            let keep = items.len() % 2;
+            let offset = self.coin();
+            let promoted: Vec<T> = items
+                .drain(keep..)
+                .enumerate()
+                .filter_map(|(i, x)| (i % 2 == offset).then_some(x))
+                .collect();
+
+            self.retained -= promoted.len();
+            self.levels[h + 1].extend(promoted);
+            self.levels[h] = items;
+            return;
+        }
+    }
+
+    /// Returns 0 or 1 with equal probability.
+    fn coin(&mut self) -> usize {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 63) as usize
+    }
+
+    /// Every retained item with its weight, in sorted order.
+    fn weighted_items(&self) -> Vec<(&T, usize)> {
+        let mut items: Vec<(&T, usize)> = self
+            .levels
+            .iter()
+            .enumerate()
+            .flat_map(|(h, items)| items.iter().map(move |x| (x, 1 << h)))
+            .collect();
+        items.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        items
+    }
+}
+
+/// Scrambles `seed` so that nearby seeds start the xorshift state far apart.
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl<T: Ord + Clone> Default for KllSketch<T> {
+    fn default() -> Self {
+        Self::new(DEFAULT_K)
+    }
+}
+
+impl<T: Ord + Clone> Extend<T> for KllSketch<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for x in iter {
+            self.insert(x);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::select::select_nth;
+    use rand::prelude::*;
+    use rand_chacha::ChaCha8Rng;
+
+    /// Normalized rank error allowed by the tests, the 1.65% bound for `k = 200`.
+    const EPSILON: f64 = 0.0165;
+
+    /// `n` keys below one million from a seeded generator.
+    fn random_keys(seed: u64, n: usize) -> Vec<u64> {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        (0..n).map(|_| rng.random_range(0..1_000_000)).collect()
+    }
+
+    /// Checks every quantile answer against the exact order statistics
+    /// `EPSILON * n` ranks to either side of the target.
+    fn assert_quantiles(sketch: &KllSketch<u64>, data: &[u64]) {
+        let n = data.len();
+        let slack = (EPSILON * n as f64) as usize;
+        for q in [
+            0.0, 0.001, 0.01, 0.1, 0.25, 0.5, 0.75, 0.9, 0.99, 0.999, 1.0,
+        ] {
+            let target = (q * (n - 1) as f64) as usize;
+            let mut work = data.to_vec();
+            let lo = *select_nth(&mut work, target.saturating_sub(slack));
+            let hi = *select_nth(&mut work, (target + slack).min(n - 1));
+
+            let estimate = sketch.quantile(q).unwrap();
+            assert!(
+                lo <= estimate && estimate <= hi,
+                "q = {q}: {lo} <= {estimate} <= {hi}"
+            );
+        }
+    }
+
+    #[test]
+    fn empty_sketch() {
+        let sketch = KllSketch::<u32>::default();
+        assert!(sketch.is_empty());
+        assert_eq!(sketch.quantile(0.5), None);
+        assert_eq!(sketch.rank(&7), 0);
+    }
+
+    #[test]
+    fn small_streams_are_exact() {
+        let mut sketch = KllSketch::default();
+        sketch.extend((0..100u32).rev());
+        assert_eq!(sketch.retained(), 100);
+        assert_eq!(sketch.quantile(0.0), Some(0));
+        assert_eq!(sketch.quantile(0.5), Some(49));
+        assert_eq!(sketch.quantile(1.0), Some(99));
+        assert_eq!(sketch.rank(&9), 10);
+    }
+
+    #[test]
+    fn quantiles_within_bound() {
+        let data = random_keys(0x9e37_79b9_7f4a_7c15, 200_000);
+        let mut sketch = KllSketch::with_seed(DEFAULT_K, 1);
+        sketch.extend(data.iter().copied());
+
+        assert_eq!(sketch.len(), data.len());
+        assert!(
+            sketch.retained() < 4 * DEFAULT_K,
+            "{} retained",
+            sketch.retained()
+        );
+        assert_quantiles(&sketch, &data);
+    }
+
+    #[test]
+    fn sorted_stream_within_bound() {
+        let data: Vec<u64> = (0..100_000).collect();
+        let mut sketch = KllSketch::with_seed(DEFAULT_K, 2);
+        sketch.extend(data.iter().copied());
+        assert_quantiles(&sketch, &data);
+    }
+
+    #[test]
+    fn rank_within_bound() {
+        let data = random_keys(3, 100_000);
+        let mut sketch = KllSketch::with_seed(DEFAULT_K, 3);
+        sketch.extend(data.iter().copied());
+
+        let slack = (EPSILON * data.len() as f64) as usize;
+        for x in (0..1_000_000).step_by(50_000) {
+            let exact = data.iter().filter(|&&y| y <= x).count();
+            assert!(sketch.rank(&x).abs_diff(exact) <= slack, "rank of {x}");
+        }
+    }
+
+    #[test]
+    fn merged_sketches_within_bound() {
+        let data = random_keys(11, 200_000);
+        let mut merged = KllSketch::with_seed(DEFAULT_K, 0);
+        for (seed, chunk) in (1..).zip(data.chunks(30_000)) {
+            let mut sketch = KllSketch::with_seed(DEFAULT_K, seed);
+            sketch.extend(chunk.iter().copied());
+            merged.merge(sketch);
+        }
+
+        assert_eq!(merged.len(), data.len());
+        assert!(
+            merged.retained() < 4 * DEFAULT_K,
+            "{} retained",
+            merged.retained()
+        );
+        assert_quantiles(&merged, &data);
+    }
+
+    #[test]
+    #[should_panic(expected = "different k")]
+    fn merge_rejects_different_k() {
+        let mut a = KllSketch::<u32>::new(200);
+        a.merge(KllSketch::new(100));
+    }
+}
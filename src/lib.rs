@@ -1,49 +1,61 @@
-#![feature(core_intrinsics)]
-#![feature(unsafe_cell_access)]
+#![cfg_attr(feature = "nightly", allow(internal_features))]
+#![cfg_attr(feature = "nightly", feature(core_intrinsics))]
+#![cfg_attr(feature = "nightly", feature(unsafe_cell_access))]
 pub mod ebr;
 pub mod ebrq;
+pub mod edyn;
+pub mod emap;
+pub mod eset;
+pub mod eview;
+pub mod kll;
 pub mod nblfq;
 pub mod sch;
 pub mod select;
+pub mod stree;
+
+use std::cmp::Ordering;
+use std::ops::{Bound, RangeBounds};
 
 pub struct EytzingerTree<T> {
     data: Vec<T>,
 }
 
-impl<T: Ord + Copy + Default> EytzingerTree<T> {
-    pub fn new(sorted: &[T]) -> Self {
-        let mut data = vec![T::default(); sorted.len()];
-        let mut index = 0;
-        Self::build(&mut data, 0, sorted, &mut index);
-        Self { data }
+/// Number of keys [`EytzingerTree::search_batch`] keeps in flight.
+const SEARCH_BATCH: usize = 16;
+
+impl<T: Ord> EytzingerTree<T> {
+    /// Builds the tree from a sorted vector, moving every element into place.
+    pub fn new(sorted: Vec<T>) -> Self {
+        Self::new_by(sorted, T::cmp)
     }
 
-    /// Converts sorted slice into 0-indexed Eytzinger layout
-    fn build(data: &mut [T], k: usize, sorted: &[T], index: &mut usize) {
-        if k >= data.len() {
-            return;
+    /// Builds the tree from an iterator yielding elements in sorted order.
+    ///
+    /// Iterators that report an exact size are laid out directly, others are
+    /// collected first.
+    pub fn from_sorted_iter<I: IntoIterator<Item = T>>(sorted: I) -> Self {
+        let sorted = sorted.into_iter();
+        match sorted.size_hint() {
+            (lower, Some(upper)) if lower == upper => {
+                let tree = Self {
+                    data: eytzinger_layout(sorted, lower),
+                };
+                debug_assert!(tree.is_sorted_by(T::cmp), "input must be sorted");
+                tree
+            }
+            _ => Self::new(sorted.collect()),
         }
-
-        // Left subtree
-        Self::build(data, 2 * k + 1, sorted, index);
-
-        // Current node
-        data[k] = sorted[*index];
-        *index += 1;
-
-        // Right subtree
-        Self::build(data, 2 * k + 2, sorted, index);
     }
 
-    pub fn search(&self, target: T) -> Option<usize> {
+    pub fn search(&self, target: &T) -> Option<usize> {
         let n = self.data.len();
         let mut i = 0;
 
         // Navigate to leaf level
         while i < n {
-            if target < self.data[i] {
+            if *target < self.data[i] {
                 i = 2 * i + 1; // go left
-            } else if target > self.data[i] {
+            } else if *target > self.data[i] {
                 i = 2 * i + 2; // go right
             } else {
                 return Some(i); // found it
@@ -53,7 +65,7 @@ impl<T: Ord + Copy + Default> EytzingerTree<T> {
         None
     }
 
-    pub fn search_optimized(&self, target: T) -> Option<usize> {
+    pub fn search_optimized(&self, target: &T) -> Option<usize> {
         let n = self.data.len();
         let mut i = 0;
         let mut candidate = n; // Invalid index means "not found"
@@ -61,20 +73,17 @@ impl<T: Ord + Copy + Default> EytzingerTree<T> {
         while i < n {
             // Branchless: update candidate if we found target
             // If data[i] == target, use i, otherwise keep candidate
-            let is_match = (self.data[i] == target) as usize;
+            let is_match = (self.data[i] == *target) as usize;
             candidate = is_match * i + (1 - is_match) * candidate;
 
             // Branch-free navigation
-            i = 2 * i + 1 + (self.data[i] < target) as usize;
+            i = 2 * i + 1 + (self.data[i] < *target) as usize;
         }
 
         if candidate < n { Some(candidate) } else { None }
     }
-    pub fn as_ref(&self) -> &[T] {
-        &self.data
-    }
 
-    pub fn search_prefetch(&self, target: T) -> Option<usize> {
+    pub fn search_prefetch(&self, target: &T) -> Option<usize> {
         let n = self.data.len();
         let mut i = 0;
         let mut candidate = n;
@@ -85,26 +94,545 @@ impl<T: Ord + Copy + Default> EytzingerTree<T> {
             let right = 2 * i + 2;
 
             if left < n {
-                unsafe {
-                    std::intrinsics::prefetch_read_data::<T, 3>(self.data.as_ptr().add(left));
-                }
+                prefetch(&self.data[left]);
             }
             if right < n {
-                unsafe {
-                    std::intrinsics::prefetch_read_data::<T, 3>(self.data.as_ptr().add(right));
-                }
+                prefetch(&self.data[right]);
             }
 
             // Branchless search
-            let is_match = (self.data[i] == target) as usize;
+            let is_match = (self.data[i] == *target) as usize;
             candidate = is_match * i + (1 - is_match) * candidate;
 
-            let go_right = (self.data[i] < target) as usize;
+            let go_right = (self.data[i] < *target) as usize;
             i = 2 * i + 1 + go_right;
         }
 
         if candidate < n { Some(candidate) } else { None }
     }
+
+    /// Searches for every key in `keys`, writing the position of its first
+    /// occurrence in sorted order, or `None`, into the matching slot of `out`.
+    ///
+    /// With duplicate keys this is the copy [`search_rank`](Self::search_rank)
+    /// reports, which need not be the one [`search`](Self::search) stops at.
+    ///
+    /// Keys are processed in groups of sixteen that descend the tree in lockstep,
+    /// one level per round. The loads issued in a round are independent of each
+    /// other so their cache misses overlap, and each lane also prefetches the
+    /// cache line holding its descendants four levels down.
+    ///
+    /// Panics if `keys` and `out` differ in length.
+    pub fn search_batch(&self, keys: &[T], out: &mut [Option<usize>]) {
+        assert_eq!(keys.len(), out.len(), "keys and out must have equal length");
+        let n = self.data.len();
+        if n == 0 {
+            out.fill(None);
+            return;
+        }
+
+        // Every level above `h` is full, only the last one needs a bounds check.
+        let h = n.ilog2();
+
+        for (keys, out) in keys.chunks(SEARCH_BATCH).zip(out.chunks_mut(SEARCH_BATCH)) {
+            let mut k = [1usize; SEARCH_BATCH];
+            let k = &mut k[..keys.len()];
+
+            for _ in 0..h {
+                for (k, key) in k.iter_mut().zip(keys) {
+                    let ahead = 16 * *k - 1;
+                    if ahead < n {
+                        prefetch(&self.data[ahead]);
+                    }
+                    *k = 2 * *k + (self.data[*k - 1] < *key) as usize;
+                }
+            }
+
+            for ((k, key), out) in k.iter_mut().zip(keys).zip(out.iter_mut()) {
+                if *k <= n {
+                    *k = 2 * *k + (self.data[*k - 1] < *key) as usize;
+                }
+                *k >>= k.trailing_ones() + 1;
+
+                *out = if *k != 0 && self.data[*k - 1] == *key {
+                    Some(*k - 1)
+                } else {
+                    None
+                };
+            }
+        }
+    }
+
+    /// Returns the rank of the first element that is not less than `target`, or
+    /// `len` if every element is less than `target`.
+    pub fn lower_bound(&self, target: &T) -> usize {
+        let n = self.data.len();
+        match self.lower_bound_index(target) {
+            i if i < n => eytzinger_to_sorted(n, i),
+            _ => n,
+        }
+    }
+
+    /// Returns the rank of `target` in the sorted order, or `None` if it is not
+    /// present. With duplicates the rank of the first occurrence is returned.
+    pub fn search_rank(&self, target: &T) -> Option<usize> {
+        let n = self.data.len();
+        let i = self.lower_bound_index(target);
+        if i < n && self.data[i] == *target {
+            Some(eytzinger_to_sorted(n, i))
+        } else {
+            None
+        }
+    }
+
+    /// Position in `data` of the first element that is not less than `target`,
+    /// or `len` if there is none.
+    fn lower_bound_index(&self, target: &T) -> usize {
+        lower_bound_index(&self.data, target)
+    }
+
+    /// Returns the rank of the first element that is greater than `target`, or
+    /// `len` if no element is greater than `target`.
+    pub fn upper_bound(&self, target: &T) -> usize {
+        self.partition_point(|x| x <= target)
+    }
+
+    /// Returns the rank of the largest element strictly less than `target`.
+    pub fn predecessor(&self, target: &T) -> Option<usize> {
+        self.lower_bound(target).checked_sub(1)
+    }
+
+    /// Returns the rank of the smallest element strictly greater than `target`.
+    pub fn successor(&self, target: &T) -> Option<usize> {
+        let rank = self.upper_bound(target);
+        if rank < self.data.len() {
+            Some(rank)
+        } else {
+            None
+        }
+    }
+
+    /// Iterates in sorted order over the elements that fall within `range`.
+    ///
+    /// Both ends are located with a single descent each; the iterator then
+    /// steps between in-order neighbours directly in the Eytzinger layout.
+    pub fn range<R: RangeBounds<T>>(&self, range: R) -> Iter<'_, T> {
+        let start = match range.start_bound() {
+            Bound::Included(x) => self.lower_bound(x),
+            Bound::Excluded(x) => self.upper_bound(x),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(x) => self.upper_bound(x),
+            Bound::Excluded(x) => self.lower_bound(x),
+            Bound::Unbounded => self.data.len(),
+        };
+        Iter::new(&self.data, start, end)
+    }
+}
+
+impl<T> AsRef<[T]> for EytzingerTree<T> {
+    /// Returns the elements in Eytzinger order.
+    fn as_ref(&self) -> &[T] {
+        &self.data
+    }
+}
+
+impl<T> EytzingerTree<T> {
+    /// Builds the tree from a vector sorted according to `compare`.
+    pub fn new_by<F>(sorted: Vec<T>, mut compare: F) -> Self
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        debug_assert!(
+            sorted.is_sorted_by(|a, b| compare(a, b) != Ordering::Greater),
+            "input must be sorted"
+        );
+        let n = sorted.len();
+        Self {
+            data: eytzinger_layout(sorted.into_iter(), n),
+        }
+    }
+
+    /// Builds the tree from a vector sorted by the key `f` extracts.
+    pub fn new_by_key<K, F>(sorted: Vec<T>, mut f: F) -> Self
+    where
+        K: Ord,
+        F: FnMut(&T) -> K,
+    {
+        Self::new_by(sorted, |a, b| f(a).cmp(&f(b)))
+    }
+
+    /// Checks that an in-order walk of the layout is sorted by `compare`.
+    fn is_sorted_by<F>(&self, mut compare: F) -> bool
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        let n = self.data.len();
+        (1..n).all(|rank| {
+            let prev = &self.data[sorted_to_eytzinger(n, rank - 1)];
+            let next = &self.data[sorted_to_eytzinger(n, rank)];
+            compare(prev, next) != Ordering::Greater
+        })
+    }
+
+    /// Searches with a comparator that returns the ordering of an element
+    /// relative to the target, as in [`slice::binary_search_by`]. Returns the
+    /// position of a match in the Eytzinger layout.
+    pub fn search_by<F>(&self, mut f: F) -> Option<usize>
+    where
+        F: FnMut(&T) -> Ordering,
+    {
+        let i = partition_index(&self.data, |x| f(x) == Ordering::Less);
+        if i < self.data.len() && f(&self.data[i]) == Ordering::Equal {
+            Some(i)
+        } else {
+            None
+        }
+    }
+
+    /// Searches for the element whose key, as extracted by `f`, equals `key`.
+    /// Returns the position of a match in the Eytzinger layout.
+    pub fn search_by_key<K, F>(&self, key: &K, mut f: F) -> Option<usize>
+    where
+        K: Ord,
+        F: FnMut(&T) -> K,
+    {
+        self.search_by(|x| f(x).cmp(key))
+    }
+
+    /// Returns the rank of the first element for which `f` does not return
+    /// [`Ordering::Less`].
+    pub fn lower_bound_by<F>(&self, mut f: F) -> usize
+    where
+        F: FnMut(&T) -> Ordering,
+    {
+        self.partition_point(|x| f(x) == Ordering::Less)
+    }
+
+    /// Returns the rank of the first element whose key is not less than `key`.
+    pub fn lower_bound_by_key<K, F>(&self, key: &K, mut f: F) -> usize
+    where
+        K: Ord,
+        F: FnMut(&T) -> K,
+    {
+        self.partition_point(|x| f(x) < *key)
+    }
+
+    /// Returns the rank of the first element for which `f` returns
+    /// [`Ordering::Greater`].
+    pub fn upper_bound_by<F>(&self, mut f: F) -> usize
+    where
+        F: FnMut(&T) -> Ordering,
+    {
+        self.partition_point(|x| f(x) != Ordering::Greater)
+    }
+
+    /// Returns the rank of the first element whose key is greater than `key`.
+    pub fn upper_bound_by_key<K, F>(&self, key: &K, mut f: F) -> usize
+    where
+        K: Ord,
+        F: FnMut(&T) -> K,
+    {
+        self.partition_point(|x| f(x) <= *key)
+    }
+
+    /// Returns the rank of the first element for which `pred` is false, with
+    /// the same contract as [`slice::partition_point`]: the tree must hold every
+    /// element satisfying `pred` before every element that does not.
+    pub fn partition_point<P>(&self, pred: P) -> usize
+    where
+        P: FnMut(&T) -> bool,
+    {
+        let n = self.data.len();
+        match partition_index(&self.data, pred) {
+            i if i < n => eytzinger_to_sorted(n, i),
+            _ => n,
+        }
+    }
+
+    /// Returns the element of rank `rank` in sorted order.
+    pub fn get_by_rank(&self, rank: usize) -> Option<&T> {
+        if rank < self.data.len() {
+            Some(&self.data[sorted_to_eytzinger(self.data.len(), rank)])
+        } else {
+            None
+        }
+    }
+
+    /// Iterates over all elements in sorted order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter::new(&self.data, 0, self.data.len())
+    }
+
+    /// Returns the number of elements in the tree.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns `true` if the tree holds no elements.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Converts a position in the Eytzinger layout, as returned by the `search`
+    /// family, into the element's rank in the original sorted order.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn eytzinger_to_sorted(&self, index: usize) -> usize {
+        assert!(index < self.data.len(), "index out of bounds");
+        eytzinger_to_sorted(self.data.len(), index)
+    }
+
+    /// Converts a rank in the original sorted order into the position of that
+    /// element in the Eytzinger layout.
+    ///
+    /// Panics if `rank` is out of bounds.
+    pub fn sorted_to_eytzinger(&self, rank: usize) -> usize {
+        assert!(rank < self.data.len(), "rank out of bounds");
+        sorted_to_eytzinger(self.data.len(), rank)
+    }
+}
+
+impl<'a, T> IntoIterator for &'a EytzingerTree<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Sorted-order iterator over the elements of an [`EytzingerTree`], created by
+/// [`EytzingerTree::iter`] and [`EytzingerTree::range`].
+pub struct Iter<'a, T> {
+    data: &'a [T],
+    /// Position in `data` of the next element from the front.
+    front: usize,
+    /// Position in `data` of the next element from the back.
+    back: usize,
+    /// Elements left between `front` and `back`, both included.
+    remaining: usize,
+}
+
+impl<'a, T> Iter<'a, T> {
+    /// Iterates over the ranks `start..end`.
+    fn new(data: &'a [T], start: usize, end: usize) -> Self {
+        let n = data.len();
+        if start >= end {
+            return Self {
+                data,
+                front: n,
+                back: n,
+                remaining: 0,
+            };
+        }
+        Self {
+            data,
+            front: sorted_to_eytzinger(n, start),
+            back: sorted_to_eytzinger(n, end - 1),
+            remaining: end - start,
+        }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let item = &self.data[self.front];
+        self.remaining -= 1;
+        if self.remaining > 0 {
+            self.front = next_in_order(self.data.len(), self.front);
+        }
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> DoubleEndedIterator for Iter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let item = &self.data[self.back];
+        self.remaining -= 1;
+        if self.remaining > 0 {
+            self.back = prev_in_order(self.data.len(), self.back);
+        }
+        Some(item)
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+impl<T> std::iter::FusedIterator for Iter<'_, T> {}
+
+/// Position of the in-order successor of position `i` in an Eytzinger layout
+/// of `n` elements, which must exist.
+///
+/// With a right child the successor is the leftmost node of the right subtree;
+/// otherwise it is the first ancestor reached from its left subtree, found by
+/// shifting out the trailing ones (right turns) plus one (1-indexed).
+fn next_in_order(n: usize, i: usize) -> usize {
+    let mut k = i + 1;
+    if 2 * k < n {
+        k = 2 * k + 1;
+        while 2 * k <= n {
+            k *= 2;
+        }
+    } else {
+        k >>= k.trailing_ones() + 1;
+    }
+    debug_assert!(k != 0, "no successor");
+    k - 1
+}
+
+/// Position of the in-order predecessor of position `i` in an Eytzinger layout
+/// of `n` elements, which must exist. Mirror image of [`next_in_order`].
+fn prev_in_order(n: usize, i: usize) -> usize {
+    let mut k = i + 1;
+    if 2 * k <= n {
+        k *= 2;
+        while 2 * k < n {
+            k = 2 * k + 1;
+        }
+    } else {
+        k >>= k.trailing_zeros() + 1;
+    }
+    debug_assert!(k != 0, "no predecessor");
+    k - 1
+}
+
+/// Hints the CPU to pull the cache line holding `ptr` into every cache level.
+///
+/// Uses the `prefetch_read_data` intrinsic with the `nightly` feature, the
+/// architecture's prefetch instruction on stable x86_64 and aarch64, and does
+/// nothing elsewhere.
+#[inline(always)]
+fn prefetch<T>(ptr: *const T) {
+    #[cfg(feature = "nightly")]
+    std::intrinsics::prefetch_read_data::<T, 3>(ptr);
+
+    #[cfg(all(not(feature = "nightly"), target_arch = "x86_64"))]
+    unsafe {
+        use std::arch::x86_64::{_MM_HINT_T0, _mm_prefetch};
+        _mm_prefetch::<_MM_HINT_T0>(ptr as *const i8);
+    }
+
+    #[cfg(all(not(feature = "nightly"), target_arch = "aarch64"))]
+    unsafe {
+        std::arch::asm!(
+            "prfm pldl1keep, [{ptr}]",
+            ptr = in(reg) ptr,
+            options(nostack, preserves_flags, readonly)
+        );
+    }
+
+    #[cfg(all(
+        not(feature = "nightly"),
+        not(any(target_arch = "x86_64", target_arch = "aarch64"))
+    ))]
+    let _ = ptr;
+}
+
+/// Position of the first element of the Eytzinger layout `data` that is not
+/// less than `target`, or `data.len()` if there is none.
+fn lower_bound_index<T: Ord>(data: &[T], target: &T) -> usize {
+    partition_index(data, |x| x < target)
+}
+
+/// Position of the first element of the Eytzinger layout `data`, in sorted
+/// order, for which `pred` is false, or `data.len()` if there is none.
+///
+/// The descent always runs to the bottom of the tree: every right turn appends
+/// a 1 to the (1-indexed) node index and every left turn a 0, so the last left
+/// turn, which is the answer, is recovered by shifting out the trailing ones
+/// plus the final zero.
+fn partition_index<T>(data: &[T], mut pred: impl FnMut(&T) -> bool) -> usize {
+    let n = data.len();
+    let mut k = 1;
+
+    while k <= n {
+        k = 2 * k + pred(&data[k - 1]) as usize;
+    }
+    k >>= k.trailing_ones() + 1;
+
+    if k == 0 { n } else { k - 1 }
+}
+
+/// Moves the `n` elements of a sorted iterator into Eytzinger order, writing
+/// each one straight into its final slot of a single allocation.
+///
+/// Panics if the iterator does not yield exactly `n` elements.
+fn eytzinger_layout<T>(sorted: impl Iterator<Item = T>, n: usize) -> Vec<T> {
+    let mut data: Vec<std::mem::MaybeUninit<T>> = Vec::with_capacity(n);
+    // SAFETY: `MaybeUninit` needs no initialization.
+    unsafe { data.set_len(n) };
+
+    let mut written = 0;
+    for (rank, value) in sorted.enumerate() {
+        assert!(rank < n, "iterator yielded more than {n} elements");
+        data[sorted_to_eytzinger(n, rank)].write(value);
+        written += 1;
+    }
+    assert_eq!(written, n, "iterator yielded fewer than {n} elements");
+
+    // SAFETY: `sorted_to_eytzinger` is a bijection on `0..n` and every rank in
+    // `0..n` was written exactly once.
+    let mut data = std::mem::ManuallyDrop::new(data);
+    unsafe { Vec::from_raw_parts(data.as_mut_ptr() as *mut T, n, data.capacity()) }
+}
+
+/// Maps a 0-indexed position in an Eytzinger layout of `n` elements to its rank
+/// in the original sorted order.
+///
+/// The layout is a complete binary tree of height `h` whose last level holds the
+/// `m` leftmost leaves. In the perfect tree of the same height the in-order
+/// position of node `k` at depth `d` is `((2 * (k - 2^d) + 1) << (h - d)) - 1`,
+/// and the missing leaves sit at the even positions after the first `m` of them.
+fn eytzinger_to_sorted(n: usize, i: usize) -> usize {
+    debug_assert!(i < n);
+    let h = n.ilog2();
+    let m = n - (1 << h) + 1;
+
+    let k = i + 1;
+    let d = k.ilog2();
+    let p = ((2 * (k - (1 << d)) + 1) << (h - d)) - 1;
+
+    // Leaves preceding `p` in the perfect tree, of which only `m` exist.
+    let leaves = p.div_ceil(2);
+    p - leaves.saturating_sub(m)
+}
+
+/// Inverse of [`eytzinger_to_sorted`]: maps a rank to its 0-indexed position in
+/// an Eytzinger layout of `n` elements.
+///
+/// Ranks below `2m` are interleaved with the existing leaves and coincide with
+/// their in-order position in the perfect tree; past that every rank is an
+/// internal node sitting at an odd position. The depth of the node is then read
+/// off the trailing zeros of the 1-based position.
+fn sorted_to_eytzinger(n: usize, rank: usize) -> usize {
+    debug_assert!(rank < n);
+    let h = n.ilog2();
+    let m = n - (1 << h) + 1;
+
+    let p = if rank < 2 * m {
+        rank
+    } else {
+        2 * (rank - m) + 1
+    };
+    let q = p + 1;
+    let t = q.trailing_zeros();
+
+    let k = (1 << (h - t)) + (q >> (t + 1));
+    k - 1
 }
 #[cfg(test)]
 mod tests {
@@ -124,36 +652,346 @@ mod tests {
             //
             // Position: [0,  1,  2,  3,  4,  5,  6,  7]
             // Value:    [4,  2,  6,  1,  3,  5,  7]
-            let eytz = EytzingerTree::new(&sorted);
+            let eytz = EytzingerTree::new(sorted.clone());
             assert_eq!(eytz.as_ref(), vec![4, 2, 6, 1, 3, 5, 7]);
         }
 
         {
             let sorted = vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
             let expected = vec![6, 3, 8, 1, 5, 7, 9, 0, 2, 4];
-            assert_eq!(EytzingerTree::new(&sorted).as_ref(), expected);
+            assert_eq!(EytzingerTree::new(sorted.clone()).as_ref(), expected);
         }
     }
 
     #[test]
     fn test_search() {
         let sorted = vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
-        let tree = EytzingerTree::new(&sorted);
+        let tree = EytzingerTree::new(sorted.clone());
 
         // Should find all elements
         for i in 0..10 {
-            assert!(tree.search(i).is_some());
-            assert!(tree.search_optimized(i).is_some());
-            assert!(tree.search_prefetch(i).is_some());
+            assert!(tree.search(&i).is_some());
+            assert!(tree.search_optimized(&i).is_some());
+            assert!(tree.search_prefetch(&i).is_some());
         }
         println!("{:?}", tree.data);
 
         // Should not find these
-        assert!(tree.search(10).is_none());
-        assert!(tree.search(-1).is_none());
-        assert!(tree.search_optimized(10).is_none());
-        assert!(tree.search_optimized(-1).is_none());
-        assert!(tree.search_prefetch(10).is_none());
-        assert!(tree.search_prefetch(-1).is_none());
+        assert!(tree.search(&10).is_none());
+        assert!(tree.search(&-1).is_none());
+        assert!(tree.search_optimized(&10).is_none());
+        assert!(tree.search_optimized(&-1).is_none());
+        assert!(tree.search_prefetch(&10).is_none());
+        assert!(tree.search_prefetch(&-1).is_none());
+    }
+
+    #[test]
+    fn test_non_copy_keys() {
+        let words = ["apple", "banana", "cherry", "date", "elderberry"];
+        let sorted: Vec<String> = words.iter().map(|w| w.to_string()).collect();
+        let tree = EytzingerTree::new(sorted);
+
+        for (rank, word) in words.iter().enumerate() {
+            assert_eq!(tree.search_rank(&word.to_string()), Some(rank));
+        }
+        assert_eq!(tree.lower_bound(&"blueberry".to_string()), 2);
+        assert!(tree.search(&"fig".to_string()).is_none());
+    }
+
+    #[test]
+    fn test_from_sorted_iter() {
+        for n in 0..100usize {
+            let expected = EytzingerTree::new((0..n).collect());
+
+            // Exact size hint is laid out directly.
+            let exact = EytzingerTree::from_sorted_iter(0..n);
+            assert_eq!(exact.as_ref(), expected.as_ref());
+
+            // Unknown size is collected first.
+            let filtered = EytzingerTree::from_sorted_iter((0..2 * n).filter(|x| x % 2 == 0));
+            assert_eq!(filtered.len(), n);
+            assert!(filtered.is_sorted_by(Ord::cmp));
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "input must be sorted")]
+    fn test_unsorted_input_panics() {
+        EytzingerTree::new(vec![3, 1, 2]);
+    }
+
+    #[test]
+    fn test_large_build() {
+        // Deep enough that the old recursive build would have been a concern.
+        let n = 1 << 20;
+        let tree = EytzingerTree::from_sorted_iter(0..n as u32);
+        for rank in (0..n).step_by(997) {
+            assert_eq!(tree.search_rank(&(rank as u32)), Some(rank));
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Event {
+        timestamp: u64,
+        name: &'static str,
+    }
+
+    #[test]
+    fn test_new_by_key() {
+        let events = vec![
+            Event {
+                timestamp: 10,
+                name: "boot",
+            },
+            Event {
+                timestamp: 25,
+                name: "login",
+            },
+            Event {
+                timestamp: 25,
+                name: "mount",
+            },
+            Event {
+                timestamp: 40,
+                name: "logout",
+            },
+        ];
+        let tree = EytzingerTree::new_by_key(events, |e| e.timestamp);
+
+        let i = tree.search_by_key(&40, |e| e.timestamp).unwrap();
+        assert_eq!(tree.as_ref()[i].name, "logout");
+        assert!(tree.search_by_key(&11, |e| e.timestamp).is_none());
+
+        assert_eq!(tree.lower_bound_by_key(&25, |e| e.timestamp), 1);
+        assert_eq!(tree.upper_bound_by_key(&25, |e| e.timestamp), 3);
+        assert_eq!(tree.lower_bound_by_key(&41, |e| e.timestamp), 4);
+
+        let first = tree.get_by_rank(1).unwrap();
+        assert_eq!(first.name, "login");
+        assert!(tree.get_by_rank(4).is_none());
+    }
+
+    #[test]
+    fn test_new_by_descending() {
+        let sorted: Vec<i32> = (0..50).rev().collect();
+        let tree = EytzingerTree::new_by(sorted.clone(), |a, b| b.cmp(a));
+
+        for (rank, &x) in sorted.iter().enumerate() {
+            let i = tree.search_by(|e| x.cmp(e)).unwrap();
+            assert_eq!(tree.as_ref()[i], x);
+            assert_eq!(tree.lower_bound_by(|e| x.cmp(e)), rank);
+            assert_eq!(tree.upper_bound_by(|e| x.cmp(e)), rank + 1);
+        }
+        assert!(tree.search_by(|e| 50.cmp(e)).is_none());
+        assert_eq!(tree.lower_bound_by(|e| (-1).cmp(e)), 50);
+    }
+
+    #[test]
+    fn test_in_order_stepping() {
+        for n in 1..130usize {
+            let sorted: Vec<usize> = (0..n).collect();
+            let tree = EytzingerTree::new(sorted.clone());
+
+            let forward: Vec<usize> = tree.iter().copied().collect();
+            assert_eq!(forward, sorted, "n = {n}");
+
+            let backward: Vec<usize> = tree.iter().rev().copied().collect();
+            assert_eq!(backward, sorted.iter().rev().copied().collect::<Vec<_>>());
+        }
+        assert_eq!(EytzingerTree::<u8>::new(vec![]).iter().next(), None);
+    }
+
+    #[test]
+    fn test_range() {
+        let sorted: Vec<i32> = (0..40).map(|x| x / 2 * 3).collect();
+        let tree = EytzingerTree::new(sorted.clone());
+
+        let check = |range: (Bound<i32>, Bound<i32>)| {
+            let expected: Vec<i32> = sorted
+                .iter()
+                .copied()
+                .filter(|x| range.contains(x))
+                .collect();
+            let got: Vec<i32> = tree.range(range).copied().collect();
+            assert_eq!(got, expected, "{range:?}");
+
+            let mut got_back: Vec<i32> = tree.range(range).rev().copied().collect();
+            got_back.reverse();
+            assert_eq!(got_back, expected, "{range:?}");
+        };
+
+        for lo in -2..62 {
+            for hi in lo - 1..62 {
+                check((Bound::Included(lo), Bound::Excluded(hi)));
+                check((Bound::Excluded(lo), Bound::Included(hi)));
+            }
+            check((Bound::Included(lo), Bound::Unbounded));
+            check((Bound::Unbounded, Bound::Excluded(lo)));
+        }
+
+        assert_eq!(
+            tree.range(3..=6).copied().collect::<Vec<_>>(),
+            vec![3, 3, 6, 6]
+        );
+        assert_eq!(tree.range(..).len(), 40);
+    }
+
+    #[test]
+    fn test_range_meets_in_the_middle() {
+        let tree = EytzingerTree::new((0..10).collect());
+        let mut it = tree.range(2..8);
+
+        assert_eq!(it.next(), Some(&2));
+        assert_eq!(it.next_back(), Some(&7));
+        assert_eq!(it.next(), Some(&3));
+        assert_eq!(it.next_back(), Some(&6));
+        assert_eq!(it.len(), 2);
+        assert_eq!(it.next(), Some(&4));
+        assert_eq!(it.next_back(), Some(&5));
+        assert_eq!(it.next(), None);
+        assert_eq!(it.next_back(), None);
+    }
+
+    #[test]
+    fn test_partition_point() {
+        for n in 0..64usize {
+            let sorted: Vec<usize> = (0..n).collect();
+            let tree = EytzingerTree::new(sorted.clone());
+            for split in 0..=n {
+                assert_eq!(tree.partition_point(|&x| x < split), split);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "input must be sorted")]
+    fn test_unsorted_by_key_panics() {
+        EytzingerTree::new_by_key(vec![(1, 'b'), (0, 'a')], |p| p.0);
+    }
+
+    #[test]
+    fn test_search_batch() {
+        for n in 0..150i32 {
+            let sorted: Vec<i32> = (0..n).map(|x| x * 2).collect();
+            let tree = EytzingerTree::new(sorted.clone());
+
+            let keys: Vec<i32> = (-3..2 * n + 3).collect();
+            let mut out = vec![Some(usize::MAX); keys.len()];
+            tree.search_batch(&keys, &mut out);
+
+            for (&key, &found) in keys.iter().zip(&out) {
+                match found {
+                    Some(i) => assert_eq!(tree.as_ref()[i], key),
+                    None => assert!(tree.search(&key).is_none(), "n = {n}, key = {key}"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_batch_duplicates() {
+        let sorted = vec![1, 3, 3, 3, 3, 3, 7, 9, 9, 12];
+        let tree = EytzingerTree::new(sorted.clone());
+        let keys: Vec<i32> = (0..14).collect();
+        let mut out = vec![None; keys.len()];
+        tree.search_batch(&keys, &mut out);
+
+        for (key, &found) in keys.iter().zip(&out) {
+            let first = tree
+                .search_rank(key)
+                .map(|rank| tree.sorted_to_eytzinger(rank));
+            assert_eq!(found, first, "key = {key}");
+        }
+        // `search` stops at whichever 3 it meets first on the way down.
+        assert_ne!(tree.search(&3), out[3]);
+    }
+
+    #[test]
+    fn test_eytzinger_to_sorted() {
+        for n in 1..200usize {
+            let sorted: Vec<usize> = (0..n).collect();
+            let tree = EytzingerTree::new(sorted.clone());
+            for (i, &rank) in tree.as_ref().iter().enumerate() {
+                assert_eq!(eytzinger_to_sorted(n, i), rank, "n = {n}, i = {i}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_sorted_to_eytzinger() {
+        for n in 1..200usize {
+            let sorted: Vec<usize> = (0..n).collect();
+            let tree = EytzingerTree::new(sorted.clone());
+            for rank in 0..n {
+                let i = tree.sorted_to_eytzinger(rank);
+                assert_eq!(tree.as_ref()[i], rank, "n = {n}, rank = {rank}");
+                assert_eq!(tree.eytzinger_to_sorted(i), rank);
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_rank() {
+        let sorted = vec![1, 3, 3, 3, 7, 9];
+        let tree = EytzingerTree::new(sorted.clone());
+
+        assert_eq!(tree.search_rank(&1), Some(0));
+        assert_eq!(tree.search_rank(&3), Some(1));
+        assert_eq!(tree.search_rank(&7), Some(4));
+        assert_eq!(tree.search_rank(&9), Some(5));
+        assert_eq!(tree.search_rank(&0), None);
+        assert_eq!(tree.search_rank(&5), None);
+        assert_eq!(tree.search_rank(&10), None);
+
+        // Positions from `search` map back to the same key in the sorted order.
+        for &key in &sorted {
+            let i = tree.search(&key).unwrap();
+            assert_eq!(sorted[tree.eytzinger_to_sorted(i)], key);
+        }
+    }
+
+    #[test]
+    fn test_lower_upper_bound() {
+        // Duplicates make lower and upper bounds diverge.
+        for n in 0..100usize {
+            let sorted: Vec<i32> = (0..n as i32).map(|x| x / 3 * 2).collect();
+            let tree = EytzingerTree::new(sorted.clone());
+
+            for target in -2..(n as i32 + 2) {
+                assert_eq!(
+                    tree.lower_bound(&target),
+                    sorted.partition_point(|&x| x < target),
+                    "n = {n}, target = {target}"
+                );
+                assert_eq!(
+                    tree.upper_bound(&target),
+                    sorted.partition_point(|&x| x <= target),
+                    "n = {n}, target = {target}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_predecessor_successor() {
+        let sorted = vec![10, 20, 20, 30];
+        let tree = EytzingerTree::new(sorted.clone());
+
+        assert_eq!(tree.predecessor(&10), None);
+        assert_eq!(tree.predecessor(&11), Some(0));
+        assert_eq!(tree.predecessor(&30), Some(2));
+        assert_eq!(tree.predecessor(&31), Some(3));
+
+        assert_eq!(tree.successor(&9), Some(0));
+        assert_eq!(tree.successor(&10), Some(1));
+        assert_eq!(tree.successor(&20), Some(3));
+        assert_eq!(tree.successor(&30), None);
+
+        let empty = EytzingerTree::<i32>::new(vec![]);
+        assert_eq!(empty.lower_bound(&0), 0);
+        assert_eq!(empty.predecessor(&0), None);
+        assert_eq!(empty.successor(&0), None);
     }
 }
@@ -158,9 +158,14 @@ impl<T> Queue<T> {
                 {
                     Ok(_) => {
                         // We own this cell and can write data to the slot.
+                        #[cfg(feature = "nightly")]
                         unsafe {
                             self.slots[cell_index].replace(MaybeUninit::new(value));
                         }
+                        #[cfg(not(feature = "nightly"))]
+                        unsafe {
+                            self.slots[cell_index].get().write(MaybeUninit::new(value));
+                        }
                         cell.store(Cell::pack(cell_index as u32, counter), Ordering::Release);
                         return Ok(());
                     }
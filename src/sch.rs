@@ -325,11 +325,10 @@ impl UnchainedHashTable {
     }
 
     pub fn num_tuples(&self) -> usize {
-        if self.tuple_stride == 0 {
-            0
-        } else {
-            self.tuple_storage.len() / self.tuple_stride
-        }
+        self.tuple_storage
+            .len()
+            .checked_div(self.tuple_stride)
+            .unwrap_or(0)
     }
 }
 
@@ -356,7 +355,7 @@ pub struct BuildConfig {
 impl BuildConfig {
     pub fn new(tuple_stride: usize) -> Self {
         debug_assert!(tuple_stride >= size_of::<u64>());
-        debug_assert!(tuple_stride % size_of::<u64>() == 0);
+        debug_assert!(tuple_stride.is_multiple_of(size_of::<u64>()));
         Self {
             num_partitions_shift: 7,
             tuple_stride,
@@ -456,8 +455,7 @@ pub fn build(collectors: Vec<LocalCollector>, config: &BuildConfig) -> Unchained
     {
         let dir_ptr = SendPtr(directory.as_mut_ptr());
         thread::scope(|s| {
-            for p in 0..num_partitions {
-                let data = &partition_data[p];
+            for data in &partition_data {
                 let dir_ptr = SendPtr(dir_ptr.0);
                 s.spawn(move || {
                     let mut pos = 0;
@@ -481,9 +479,9 @@ pub fn build(collectors: Vec<LocalCollector>, config: &BuildConfig) -> Unchained
     // Phase 2: Exclusive prefix sum
     {
         let mut cumulative: u64 = 0;
-        for i in 1..directory.len() {
-            let count = directory[i].offset();
-            directory[i] = DirectoryEntry::new(cumulative, directory[i].bloom());
+        for entry in &mut directory[1..] {
+            let count = entry.offset();
+            *entry = DirectoryEntry::new(cumulative, entry.bloom());
             cumulative += count;
         }
         debug_assert_eq!(cumulative, total_bytes as u64);
@@ -494,8 +492,7 @@ pub fn build(collectors: Vec<LocalCollector>, config: &BuildConfig) -> Unchained
         let dir_ptr = SendPtr(directory.as_mut_ptr());
         let store_ptr = SendPtr(tuple_storage.as_mut_ptr());
         thread::scope(|s| {
-            for p in 0..num_partitions {
-                let data = &partition_data[p];
+            for data in &partition_data {
                 let dir_ptr = SendPtr(dir_ptr.0);
                 let store_ptr = SendPtr(store_ptr.0);
                 s.spawn(move || {
@@ -653,9 +650,9 @@ mod tests {
         }
 
         let mut cumulative: u64 = 0;
-        for i in 1..directory.len() {
-            let count = directory[i].offset();
-            directory[i] = DirectoryEntry::new(cumulative, directory[i].bloom());
+        for entry in &mut directory[1..] {
+            let count = entry.offset();
+            *entry = DirectoryEntry::new(cumulative, entry.bloom());
             cumulative += count;
         }
 
@@ -750,7 +747,7 @@ mod tests {
     fn build_multi(tuples: &[(u32, u64)], num_threads: usize) -> UnchainedHashTable {
         let config = BuildConfig::new(STRIDE);
         let collectors: Vec<LocalCollector> = thread::scope(|s| {
-            let chunk_size = (tuples.len() + num_threads - 1) / num_threads;
+            let chunk_size = tuples.len().div_ceil(num_threads);
             let handles: Vec<_> = tuples
                 .chunks(chunk_size)
                 .map(|chunk| {
@@ -1,74 +1,877 @@
+//! Selection of order statistics from unordered slices.
+//!
+//! Every function here reorders the slice so that the selected element lands
+//! at its sorted position `k`, everything before it compares less than or
+//! equal to it and everything after it compares greater than or equal to it,
+//! the same contract as [`slice::select_nth_unstable`].
+
+use std::cmp::Ordering;
+use std::thread;
+
+/// Reorders `data` so that `data[k]` is the `k`-th smallest element and
+/// returns it.
+///
+/// # Panics
+///
+/// Panics if `k >= data.len()`.
+pub fn select_nth<T: Ord>(data: &mut [T], k: usize) -> &T {
+    select_nth_by(data, k, T::cmp)
+}
+
+/// Like [`select_nth`] but orders elements with `compare`.
+pub fn select_nth_by<T, F>(data: &mut [T], k: usize, mut compare: F) -> &T
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    select_by(data, k, Strategy::default(), &mut |a, b| {
+        compare(a, b) == Ordering::Less
+    })
+}
+
+/// Like [`select_nth`] but runs the given selection [`Strategy`].
+pub fn select_nth_with<T: Ord>(data: &mut [T], k: usize, strategy: Strategy) -> &T {
+    select_by(data, k, strategy, &mut T::lt)
+}
+
+/// Algorithm used to select an order statistic.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Strategy {
+    /// Introselect: quickselect with a median-of-medians fallback, linear in the worst case.
+    #[default]
+    QuickSelect,
+    /// Floyd–Rivest: recursively selects from a small sample to pick two pivots that bracket
+    /// `k` tightly, which takes about `n + min(k, n - k)` comparisons on large inputs. Expected
+    /// linear time, without a worst-case guarantee.
+    FloydRivest,
+}
+
+fn select_by<'a, T, F>(data: &'a mut [T], k: usize, strategy: Strategy, is_less: &mut F) -> &'a T
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    let n = data.len();
+    assert!(k < n, "index {k} out of range for slice of length {n}");
+    match strategy {
+        Strategy::QuickSelect => QuickSelect::select(data, 0, n - 1, k, is_less),
+        Strategy::FloydRivest => FloydRivest::select(data, 0, n - 1, k, is_less),
+    }
+    &data[k]
+}
+
+/// Like [`select_nth`] but orders elements by the key `f` extracts.
+pub fn select_nth_by_key<T, K, F>(data: &mut [T], k: usize, mut f: F) -> &T
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    select_nth_by(data, k, |a, b| f(a).cmp(&f(b)))
+}
+
+/// Selects every rank in `ks` at once and returns the selected elements in
+/// the order of `ks`.
+///
+/// Afterwards `data` is partitioned around each requested rank. Only the
+/// sides that still contain requested ranks are partitioned further, which
+/// takes O(n log m) for `m` distinct ranks instead of `m` separate selections.
+///
+/// # Panics
+///
+/// Panics if any rank is `>= data.len()`.
+pub fn select_many<'a, T: Ord>(data: &'a mut [T], ks: &[usize]) -> Vec<&'a T> {
+    let n = data.len();
+    let mut sorted = ks.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    if let Some(&k) = sorted.last() {
+        assert!(k < n, "index {k} out of range for slice of length {n}");
+        select_ranks(data, 0, n - 1, &sorted, &mut T::lt);
+    }
+
+    let data: &'a [T] = data;
+    ks.iter().map(|&k| &data[k]).collect()
+}
+
+/// Selects the sorted, distinct ranks `ks`, all within `data[left..=right]`,
+/// by settling the middle rank and recursing into both sides.
+fn select_ranks<T, F>(data: &mut [T], left: usize, right: usize, ks: &[usize], is_less: &mut F)
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    if ks.is_empty() {
+        return;
+    }
+    let mid = ks.len() / 2;
+    let k = ks[mid];
+    QuickSelect::select(data, left, right, k, is_less);
+    if mid > 0 {
+        select_ranks(data, left, k - 1, &ks[..mid], is_less);
+    }
+    if mid + 1 < ks.len() {
+        select_ranks(data, k + 1, right, &ks[mid + 1..], is_less);
+    }
+}
+
+/// Moves the `k` largest elements to the end of `data` and returns them, in
+/// no particular order.
+///
+/// Small `k` (up to `√n`) goes through an in-place bounded heap in
+/// O(n log k), which on typical inputs touches the heap for only a few of
+/// the keys. Larger `k` goes through quickselect in O(n). Either way the
+/// slice ends up partitioned around index `n - k`.
+///
+/// # Panics
+///
+/// Panics if `k > data.len()`.
+pub fn top_k<T: Ord>(data: &mut [T], k: usize) -> &mut [T] {
+    let n = data.len();
+    assert!(k <= n, "k = {k} exceeds slice length {n}");
+    if k > 0 && k < n {
+        if k.saturating_mul(k) <= n {
+            heap_top_k(data, k);
+        } else {
+            select_nth(data, n - k);
+        }
+    }
+    &mut data[n - k..]
+}
+
+/// Keeps a min-heap of the `k` largest keys seen so far in the tail of
+/// `data`, swapping in every key from the front that beats its minimum.
+fn heap_top_k<T: Ord>(data: &mut [T], k: usize) {
+    let (rest, heap) = data.split_at_mut(data.len() - k);
+    for i in (0..k / 2).rev() {
+        sift_down(heap, i);
+    }
+    for x in rest {
+        if heap[0] < *x {
+            std::mem::swap(x, &mut heap[0]);
+            sift_down(heap, 0);
+        }
+    }
+}
+
+/// Accumulates the `k` largest items of a stream in a bounded min-heap.
+///
+/// Every push costs O(log k), and only when the item beats the smallest one
+/// kept, so memory stays at `k` items however long the stream is.
+pub struct TopK<T> {
+    k: usize,
+    /// Min-heap of the kept items.
+    heap: Vec<T>,
+}
+
+impl<T: Ord> TopK<T> {
+    /// Creates an accumulator keeping the `k` largest items.
+    pub fn new(k: usize) -> Self {
+        Self {
+            k,
+            heap: Vec::with_capacity(k),
+        }
+    }
+
+    /// Offers `x`, keeping it if it is among the `k` largest seen so far.
+    pub fn push(&mut self, x: T) {
+        if self.heap.len() < self.k {
+            let i = self.heap.len();
+            self.heap.push(x);
+            sift_up(&mut self.heap, i);
+        } else if self.k > 0 && self.heap[0] < x {
+            self.heap[0] = x;
+            sift_down(&mut self.heap, 0);
+        }
+    }
+
+    /// Folds the items kept by `other` into `self`.
+    pub fn merge(&mut self, other: Self) {
+        self.extend(other.heap);
+    }
+
+    /// Returns the smallest item kept, the threshold a new item has to beat
+    /// once `k` items are held.
+    pub fn peek_min(&self) -> Option<&T> {
+        self.heap.first()
+    }
+
+    /// Returns the number of items kept.
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    /// Returns `true` if no item is kept.
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// Returns the kept items in ascending order.
+    pub fn into_sorted_vec(mut self) -> Vec<T> {
+        self.heap.sort_unstable();
+        self.heap
+    }
+}
+
+impl<T: Ord> Extend<T> for TopK<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for x in iter {
+            self.push(x);
+        }
+    }
+}
+
+/// Restores the min-heap order of `heap` after `heap[i]` decreased.
+fn sift_up<T: Ord>(heap: &mut [T], mut i: usize) {
+    while i > 0 {
+        let parent = (i - 1) / 2;
+        if heap[i] >= heap[parent] {
+            break;
+        }
+        heap.swap(i, parent);
+        i = parent;
+    }
+}
+
+/// Restores the min-heap order of `heap` after `heap[i]` increased.
+fn sift_down<T: Ord>(heap: &mut [T], mut i: usize) {
+    loop {
+        let mut child = 2 * i + 1;
+        if child >= heap.len() {
+            break;
+        }
+        if child + 1 < heap.len() && heap[child + 1] < heap[child] {
+            child += 1;
+        }
+        if heap[i] <= heap[child] {
+            break;
+        }
+        heap.swap(i, child);
+        i = child;
+    }
+}
+
+/// Returns the median of `data`, the lower one for even lengths, or `None`
+/// if `data` is empty.
+pub fn median<T: Ord>(data: &mut [T]) -> Option<&T> {
+    if data.is_empty() {
+        return None;
+    }
+    let k = (data.len() - 1) / 2;
+    Some(select_nth(data, k))
+}
+
+/// Partition kernels, public only so that `benches/select.rs` can compare them. Not part of the
+/// stable API.
+#[doc(hidden)]
+pub mod bench {
+    use super::QuickSelect;
+
+    /// Partitions `data` around `data[pivot]` and returns the pivot's final
+    /// index. Keys before it are smaller, keys after it are not.
+    ///
+    /// This is the branchless block partition [`select_nth`](super::select_nth) is built on.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pivot >= data.len()`.
+    pub fn partition<T: Ord>(data: &mut [T], pivot: usize) -> usize {
+        let n = data.len();
+        assert!(
+            pivot < n,
+            "index {pivot} out of range for slice of length {n}"
+        );
+        QuickSelect::block_partition(data, 0, n - 1, pivot, &mut T::lt)
+    }
+
+    /// Partitions `data` three ways around `data[pivot]` and returns the
+    /// inclusive range of keys equal to it, with smaller keys before and greater
+    /// keys after.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pivot >= data.len()`.
+    pub fn partition_three_way<T: Ord>(data: &mut [T], pivot: usize) -> (usize, usize) {
+        let n = data.len();
+        assert!(
+            pivot < n,
+            "index {pivot} out of range for slice of length {n}"
+        );
+        QuickSelect::partition(data, 0, n - 1, pivot, &mut T::lt)
+    }
+}
+
+/// Like [`select_nth`] but partitions in parallel over all available cores
+/// while the range holding `k` is large, see [`ParallelSelect`].
+///
+/// # Panics
+///
+/// Panics if `k >= data.len()`.
+pub fn par_select_nth<T: Ord + Copy + Send + Sync>(data: &mut [T], k: usize) -> &T {
+    let n = data.len();
+    assert!(k < n, "index {k} out of range for slice of length {n}");
+    let threads = thread::available_parallelism().map_or(1, |t| t.get());
+    ParallelSelect::select(data, k, threads, ParallelSelect::CUTOFF);
+    &data[k]
+}
+
 /// Quickselect is a selection algorithm to find the kth smallest element in an unordered list.
 ///
 /// Quickselect has a good average-case performance and is very cache friendly and in cases where
 /// you need a Top-K when K > √N it gives better algorithmic performance due to O(N) being somewhat
 /// less than O(N log K).
+///
+/// Plain quickselect degrades to O(N²) when pivots are consistently bad, so this is an
+/// introselect: after `2 log N` rounds of middle-element pivots it switches to the
+/// median-of-medians pivot, which guarantees linear time. Each round partitions with the
+/// branchless [`partition_in_blocks`] kernel, which sends keys equal to the pivot to the right.
+/// Once the range has a lower bound and the pivot equals it, a separate pass moves every copy of
+/// the pivot to the front instead, so runs of equal keys are settled at once rather than being
+/// partitioned over and over.
 struct QuickSelect {}
 
 impl QuickSelect {
-    /// Lomuto partition scheme as used in quicksort.
-    fn partition<T: Ord>(data: &mut [T], left: usize, right: usize, pivot: usize) -> usize {
+    /// Groups of this size are collapsed into their median by median-of-medians.
+    const GROUP: usize = 5;
+
+    /// Three-way (Dutch national flag) partition of `data[left..=right]` around `data[pivot]`.
+    ///
+    /// Returns the inclusive range `(lt, gt)` holding the keys equal to the pivot, keys before
+    /// `lt` are smaller and keys after `gt` are greater.
+    fn partition<T, F>(
+        data: &mut [T],
+        left: usize,
+        right: usize,
+        pivot: usize,
+        is_less: &mut F,
+    ) -> (usize, usize)
+    where
+        F: FnMut(&T, &T) -> bool,
+    {
         if data.is_empty() || left >= right {
-            return left;
+            return (left, left);
         }
-        // Move pivot to the end.
-        data.swap(pivot, right);
-        let mut store = left;
+        // `data[lt..i]` is the run of keys equal to the pivot and is never empty, so `data[lt]`
+        // stands in for the pivot as it moves.
+        data.swap(pivot, left);
+        let mut lt = left;
+        let mut i = left + 1;
+        let mut gt = right + 1;
 
-        for i in left..right {
-            if data[i] < data[right] {
-                data.swap(store, i);
-                store += 1;
+        while i < gt {
+            if is_less(&data[i], &data[lt]) {
+                data.swap(lt, i);
+                lt += 1;
+                i += 1;
+            } else if is_less(&data[lt], &data[i]) {
+                gt -= 1;
+                data.swap(i, gt);
+            } else {
+                i += 1;
             }
         }
 
-        // Move pivot to its final place.
-        data.swap(right, store);
+        (lt, gt - 1)
+    }
 
-        store
+    /// Moves the k-th smallest element of `data[left..=right]` to index `k`.
+    ///
+    /// If `left > 0`, `data[left - 1]` must not be greater than any key in the range, which holds
+    /// whenever the range is one side of an earlier partition.
+    fn select<T, F>(data: &mut [T], left: usize, right: usize, k: usize, is_less: &mut F)
+    where
+        F: FnMut(&T, &T) -> bool,
+    {
+        let budget = 2 * (right - left + 1).ilog2() as usize;
+        Self::introselect(data, left, right, k, budget, left > 0, is_less);
     }
 
-    /// Returns the k-th smallest element of the list within left..right inclusive.
-    fn select<T: Ord + Copy>(data: &mut [T], left: usize, right: usize, k: usize) -> T {
+    /// Quickselect that falls back to median-of-medians pivots once `budget` rounds are spent.
+    ///
+    /// `bounded` says whether `data[left - 1]` is a lower bound of the range.
+    fn introselect<T, F>(
+        data: &mut [T],
+        left: usize,
+        right: usize,
+        k: usize,
+        mut budget: usize,
+        mut bounded: bool,
+        is_less: &mut F,
+    ) where
+        F: FnMut(&T, &T) -> bool,
+    {
         let mut left = left;
         let mut right = right;
         loop {
-            if left == right {
-                return data[left];
+            if left >= right {
+                return;
+            }
+
+            let pivot = if budget == 0 {
+                Self::median_of_medians(data, left, right, is_less)
+            } else {
+                // Pivot is the middle of the current range.
+                budget -= 1;
+                left + (right - left) / 2
+            };
+
+            // A pivot equal to the lower bound is the smallest key in the range, so its copies
+            // form a run at the front. Settling that run in one pass keeps duplicate-heavy
+            // inputs linear.
+            if bounded && !is_less(&data[left - 1], &data[pivot]) {
+                let end = Self::partition_equal(data, left, right, pivot, is_less);
+                if k < end {
+                    return;
+                }
+                left = end;
+                continue;
+            }
+
+            let mid = Self::block_partition(data, left, right, pivot, is_less);
+
+            if k < mid {
+                right = mid - 1;
+            } else if k > mid {
+                left = mid + 1;
+                bounded = true;
+            } else {
+                return;
+            }
+        }
+    }
+
+    /// Partitions `data[left..=right]` around `data[pivot]` with the branchless
+    /// [`partition_in_blocks`] kernel and returns the pivot's final index. Keys before it are
+    /// smaller, keys after it are not.
+    fn block_partition<T, F>(
+        data: &mut [T],
+        left: usize,
+        right: usize,
+        pivot: usize,
+        is_less: &mut F,
+    ) -> usize
+    where
+        F: FnMut(&T, &T) -> bool,
+    {
+        data.swap(left, pivot);
+        let (head, rest) = data[left..=right].split_at_mut(1);
+        let pivot = &head[0];
+        let mid = left + partition_in_blocks(rest, &mut |x| is_less(x, pivot));
+        data.swap(left, mid);
+        mid
+    }
+
+    /// Moves the keys of `data[left..=right]` equal to `data[pivot]` to the front of the range,
+    /// assuming none is smaller, and returns the index one past them.
+    fn partition_equal<T, F>(
+        data: &mut [T],
+        left: usize,
+        right: usize,
+        pivot: usize,
+        is_less: &mut F,
+    ) -> usize
+    where
+        F: FnMut(&T, &T) -> bool,
+    {
+        data.swap(left, pivot);
+        let (head, rest) = data[left..=right].split_at_mut(1);
+        let pivot = &head[0];
+        left + 1 + partition_in_blocks(rest, &mut |x| !is_less(pivot, x))
+    }
+
+    /// Returns the index of a pivot whose rank within `data[left..=right]` lies between 30% and
+    /// 70%, reordering the range in the process.
+    fn median_of_medians<T, F>(data: &mut [T], left: usize, right: usize, is_less: &mut F) -> usize
+    where
+        F: FnMut(&T, &T) -> bool,
+    {
+        let n = right - left + 1;
+        if n <= Self::GROUP {
+            insertion_sort(&mut data[left..=right], is_less);
+            return left + (n - 1) / 2;
+        }
+
+        // Gather the median of every group at the front of the range.
+        let mut medians = left;
+        for start in (left..=right).step_by(Self::GROUP) {
+            let end = (start + Self::GROUP - 1).min(right);
+            insertion_sort(&mut data[start..=end], is_less);
+            data.swap(medians, start + (end - start) / 2);
+            medians += 1;
+        }
+
+        // The medians are not bounded by the key before them. A zero budget keeps the recursion
+        // on median-of-medians pivots, which is what bounds the fallback to linear time.
+        let mid = left + (medians - left - 1) / 2;
+        Self::introselect(data, left, medians - 1, mid, 0, false, is_less);
+        mid
+    }
+}
+
+/// Floyd–Rivest selection (Algorithm 489, CACM 1975).
+///
+/// On ranges larger than [`Self::SAMPLE_CUTOFF`] it first recurses into a sample of about
+/// `n^(2/3)` keys positioned around `k`, so the partitioning pivot lands within a few keys of the
+/// target rank and each round discards nearly the whole range.
+struct FloydRivest {}
+
+impl FloydRivest {
+    /// Ranges up to this size are partitioned around `data[k]` without sampling.
+    const SAMPLE_CUTOFF: usize = 600;
+
+    /// Moves the k-th smallest element of `data[left..=right]` to index `k`.
+    fn select<T, F>(data: &mut [T], left: usize, right: usize, k: usize, is_less: &mut F)
+    where
+        F: FnMut(&T, &T) -> bool,
+    {
+        let mut left = left;
+        let mut right = right;
+        while left < right {
+            if right - left > Self::SAMPLE_CUTOFF {
+                // Select from the sample so that data[k] becomes a pivot close to rank k.
+                let n = (right - left + 1) as f64;
+                let i = (k - left + 1) as f64;
+                let z = n.ln();
+                let s = 0.5 * (2.0 * z / 3.0).exp();
+                let sd = 0.5 * (z * s * (n - s) / n).sqrt() * (i - n / 2.0).signum();
+                let sample_left = left.max((k as f64 - i * s / n + sd) as usize);
+                let sample_right = right.min((k as f64 + (n - i) * s / n + sd) as usize);
+                Self::select(data, sample_left, sample_right, k, is_less);
+            }
+
+            // Hoare partition around the key at k. It is parked at one end of the range so that
+            // data[left] <= pivot <= data[right] act as sentinels for the scans.
+            data.swap(left, k);
+            let mut pivot = left;
+            if is_less(&data[left], &data[right]) {
+                data.swap(left, right);
+                pivot = right;
+            }
+
+            let mut i = left;
+            let mut j = right;
+            while i < j {
+                data.swap(i, j);
+                // Only the first swap touches the pivot, moving it to the other end.
+                if pivot == i {
+                    pivot = j;
+                } else if pivot == j {
+                    pivot = i;
+                }
+                i += 1;
+                j -= 1;
+                while is_less(&data[i], &data[pivot]) {
+                    i += 1;
+                }
+                while is_less(&data[pivot], &data[j]) {
+                    j -= 1;
+                }
+            }
+
+            // Move the pivot to its final position j.
+            if pivot == left {
+                data.swap(left, j);
+            } else {
+                j += 1;
+                data.swap(j, right);
+            }
+
+            if j < k {
+                left = j + 1;
+            } else if j > k {
+                right = j - 1;
+            } else {
+                return;
+            }
+        }
+    }
+}
+
+/// Parallel selection over scoped threads.
+///
+/// Each round samples a pivot whose rank is close to `k`, then partitions the range three ways
+/// in the spirit of [`crate::sch::build`]:
+///
+/// 1. Every thread counts the keys of its chunk below, equal to and above the pivot.
+/// 2. Prefix sums over the counts give each chunk a disjoint destination in every bucket.
+/// 3. Every thread scatters its chunk into a scratch buffer, which is copied back in parallel.
+///
+/// Rounds continue on the bucket holding `k` until it drops below the cutoff, then sequential
+/// [`QuickSelect`] finishes the job.
+struct ParallelSelect {}
+
+impl ParallelSelect {
+    /// Ranges shorter than this are not worth spawning threads for.
+    const CUTOFF: usize = 1 << 16;
+    /// Keys sampled to choose each pivot.
+    const SAMPLE: usize = 1024;
+
+    /// Moves the k-th smallest element of `data` to index `k`.
+    fn select<T: Ord + Copy + Send + Sync>(
+        data: &mut [T],
+        k: usize,
+        threads: usize,
+        cutoff: usize,
+    ) {
+        let mut left = 0;
+        let mut right = data.len();
+        // Bad samples are unlikely but possible, so stop after as many rounds as halving would take.
+        let mut budget = 2 * data.len().max(2).ilog2() as usize;
+
+        if threads > 1 && right - left >= cutoff {
+            let mut scratch = data.to_vec();
+            while right - left >= cutoff && budget > 0 {
+                budget -= 1;
+                let range = &mut data[left..right];
+                let pivot = Self::sample_pivot(range, k - left);
+                let (less, equal) =
+                    Self::partition(range, &mut scratch[..right - left], pivot, threads);
+
+                if k < left + less {
+                    right = left + less;
+                } else if k < left + less + equal {
+                    return;
+                } else {
+                    left += less + equal;
+                }
+            }
+        }
+
+        QuickSelect::select(data, left, right - 1, k, &mut T::lt);
+    }
+
+    /// Picks the sample key whose rank within the sample matches the relative rank of `k`.
+    fn sample_pivot<T: Ord + Copy>(data: &[T], k: usize) -> T {
+        let n = data.len();
+        let s = n.min(Self::SAMPLE);
+        let mut sample: Vec<T> = (0..s).map(|i| data[i * n / s]).collect();
+        *select_nth(&mut sample, k * s / n)
+    }
+
+    /// Partitions `data` three ways around `pivot` using `scratch` as the scatter target.
+    ///
+    /// Returns the number of keys less than and equal to the pivot.
+    fn partition<T: Ord + Copy + Send + Sync>(
+        data: &mut [T],
+        scratch: &mut [T],
+        pivot: T,
+        threads: usize,
+    ) -> (usize, usize) {
+        let chunk_len = data.len().div_ceil(threads);
+
+        // Phase 1: Count per chunk.
+        let counts: Vec<[usize; 3]> = thread::scope(|s| {
+            let handles: Vec<_> = data
+                .chunks(chunk_len)
+                .map(|chunk| {
+                    s.spawn(move || {
+                        let mut count = [0; 3];
+                        for x in chunk {
+                            count[Self::bucket(x, &pivot)] += 1;
+                        }
+                        count
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        // Phase 2: Carve each bucket of the scratch buffer into per-chunk destinations.
+        let less: usize = counts.iter().map(|c| c[0]).sum();
+        let equal: usize = counts.iter().map(|c| c[1]).sum();
+        let (lo, rest) = scratch.split_at_mut(less);
+        let (eq, hi) = rest.split_at_mut(equal);
+        let mut buckets = [lo, eq, hi];
+        let destinations: Vec<[&mut [T]; 3]> = counts
+            .iter()
+            .map(|count| {
+                std::array::from_fn(|b| {
+                    let (dst, rest) = std::mem::take(&mut buckets[b]).split_at_mut(count[b]);
+                    buckets[b] = rest;
+                    dst
+                })
+            })
+            .collect();
+
+        // Phase 3: Scatter into the scratch buffer, then copy it back.
+        thread::scope(|s| {
+            for (chunk, dst) in data.chunks(chunk_len).zip(destinations) {
+                s.spawn(move || {
+                    let mut cursor = [0; 3];
+                    for &x in chunk {
+                        let b = Self::bucket(&x, &pivot);
+                        dst[b][cursor[b]] = x;
+                        cursor[b] += 1;
+                    }
+                });
             }
+        });
+        thread::scope(|s| {
+            for (chunk, src) in data.chunks_mut(chunk_len).zip(scratch.chunks(chunk_len)) {
+                s.spawn(move || chunk.copy_from_slice(src));
+            }
+        });
 
-            // Pivot is the middle of the current range.
-            let mut pivot = left + (right - left) / 2;
+        (less, equal)
+    }
 
-            pivot = Self::partition(data, left, right, pivot);
+    fn bucket<T: Ord>(x: &T, pivot: &T) -> usize {
+        match x.cmp(pivot) {
+            Ordering::Less => 0,
+            Ordering::Equal => 1,
+            Ordering::Greater => 2,
+        }
+    }
+}
 
-            if k == pivot {
-                return data[k];
-            } else if k < pivot {
-                right = pivot - 1;
+/// Keys classified per block by [`partition_in_blocks`], small enough for `u8` offsets.
+const BLOCK: usize = 128;
+
+/// Branchless block partition from BlockQuicksort (Edelkamp and Weiß, ESA 2016).
+///
+/// Moves the keys for which `goes_left` holds to the front of `v` and returns how many there
+/// are. Rather than branching on every comparison, each side classifies a block of keys into a
+/// buffer of offsets of misplaced keys, writing every offset and advancing the buffer end by
+/// the comparison result. Misplaced pairs are then exchanged with a cyclic permutation, which
+/// costs one move per key instead of the three of a swap.
+fn partition_in_blocks<T, P>(v: &mut [T], goes_left: &mut P) -> usize
+where
+    P: FnMut(&T) -> bool,
+{
+    // `v[l..r]` is not yet classified. `offsets_l[start_l..end_l]` hold the offsets from `l` of
+    // keys in the left block that belong on the right, `offsets_r` the offsets from `r - 1`
+    // of keys in the right block that belong on the left.
+    let mut l = 0;
+    let mut r = v.len();
+    let mut block_l = BLOCK;
+    let mut block_r = BLOCK;
+    let mut offsets_l = [0u8; BLOCK];
+    let mut offsets_r = [0u8; BLOCK];
+    let (mut start_l, mut end_l) = (0, 0);
+    let (mut start_r, mut end_r) = (0, 0);
+
+    loop {
+        let is_done = r - l <= 2 * BLOCK;
+        if is_done {
+            // Shrink the blocks so they exactly cover what is left, keeping any block that
+            // still has pending offsets at full size.
+            let mut rem = r - l;
+            if start_l < end_l || start_r < end_r {
+                rem -= BLOCK;
+            }
+            if start_l < end_l {
+                block_r = rem;
+            } else if start_r < end_r {
+                block_l = rem;
             } else {
-                left = pivot + 1;
+                block_l = rem / 2;
+                block_r = rem - block_l;
             }
         }
+
+        if start_l == end_l {
+            start_l = 0;
+            end_l = 0;
+            for (i, x) in v[l..l + block_l].iter().enumerate() {
+                offsets_l[end_l] = i as u8;
+                end_l += !goes_left(x) as usize;
+            }
+        }
+
+        if start_r == end_r {
+            start_r = 0;
+            end_r = 0;
+            for (i, x) in v[r - block_r..r].iter().rev().enumerate() {
+                offsets_r[end_r] = i as u8;
+                end_r += goes_left(x) as usize;
+            }
+        }
+
+        let count = (end_l - start_l).min(end_r - start_r);
+        if count > 0 {
+            let left = |i: usize| l + offsets_l[i] as usize;
+            let right = |i: usize| r - 1 - offsets_r[i] as usize;
+            let p = v.as_mut_ptr();
+            // SAFETY: the left positions lie in the left block and the right positions in the
+            // right block, which are disjoint, so every copy moves between distinct in-bounds
+            // slots. The key read into `tmp` fills the single hole left at the end, and no
+            // user code runs in between, so nothing is dropped or duplicated.
+            unsafe {
+                let tmp = std::ptr::read(p.add(left(start_l)));
+                std::ptr::copy_nonoverlapping(p.add(right(start_r)), p.add(left(start_l)), 1);
+                for _ in 1..count {
+                    start_l += 1;
+                    std::ptr::copy_nonoverlapping(p.add(left(start_l)), p.add(right(start_r)), 1);
+                    start_r += 1;
+                    std::ptr::copy_nonoverlapping(p.add(right(start_r)), p.add(left(start_l)), 1);
+                }
+                std::ptr::write(p.add(right(start_r)), tmp);
+            }
+            start_l += 1;
+            start_r += 1;
+        }
+
+        if start_l == end_l {
+            l += block_l;
+        }
+        if start_r == end_r {
+            r -= block_r;
+        }
+        if is_done {
+            break;
+        }
+    }
+
+    // At most one block still has misplaced keys, and it is all that remains of `v[l..r]`.
+    // Swap them to the far end of that block, from the last offset down.
+    if start_l < end_l {
+        while start_l < end_l {
+            end_l -= 1;
+            v.swap(l + offsets_l[end_l] as usize, r - 1);
+            r -= 1;
+        }
+        r
+    } else {
+        while start_r < end_r {
+            end_r -= 1;
+            v.swap(l, r - 1 - offsets_r[end_r] as usize);
+            l += 1;
+        }
+        l
+    }
+}
+
+fn insertion_sort<T, F>(data: &mut [T], is_less: &mut F)
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    for i in 1..data.len() {
+        let mut j = i;
+        while j > 0 && is_less(&data[j], &data[j - 1]) {
+            data.swap(j, j - 1);
+            j -= 1;
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::QuickSelect;
+    use super::*;
+    use rand::prelude::*;
+    use rand_chacha::ChaCha8Rng;
+
+    fn lt(a: &i32, b: &i32) -> bool {
+        a < b
+    }
 
     #[test]
     fn test_partition_empty_slice() {
         let mut data: Vec<i32> = vec![];
         // Can't partition empty slice meaningfully - just verify no panic
         // Don't call partition with invalid indices
-        let _ = QuickSelect::partition(data.as_mut_slice(), 0, 0, 0);
+        let _ = QuickSelect::partition(data.as_mut_slice(), 0, 0, 0, &mut lt);
     }
 
     #[test]
     fn test_partition_one_element() {
         let mut data = vec![42];
-        let pivot_pos = QuickSelect::partition(&mut data, 0, 0, 0);
+        let (pivot_pos, _) = QuickSelect::partition(&mut data, 0, 0, 0, &mut lt);
         assert_eq!(pivot_pos, 0);
         assert_eq!(data, vec![42]);
     }
@@ -76,7 +879,7 @@ mod tests {
     #[test]
     fn test_partition_two_elements_sorted() {
         let mut data = vec![1, 2];
-        let pivot_pos = QuickSelect::partition(&mut data, 0, 1, 1); // pivot=2
+        let (pivot_pos, _) = QuickSelect::partition(&mut data, 0, 1, 1, &mut lt); // pivot=2
         assert_eq!(pivot_pos, 1);
         assert!(data[0] <= data[1]);
     }
@@ -84,7 +887,7 @@ mod tests {
     #[test]
     fn test_partition_two_elements_unsorted() {
         let mut data = vec![2, 1];
-        let pivot_pos = QuickSelect::partition(&mut data, 0, 1, 1); // pivot=1
+        let (pivot_pos, _) = QuickSelect::partition(&mut data, 0, 1, 1, &mut lt); // pivot=1
         assert_eq!(pivot_pos, 0);
         assert_eq!(data, vec![1, 2]);
     }
@@ -92,7 +895,7 @@ mod tests {
     #[test]
     fn test_partition_already_sorted() {
         let mut data = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
-        let pivot_pos = QuickSelect::partition(&mut data, 0, 9, 4); // pivot value=5
+        let (pivot_pos, _) = QuickSelect::partition(&mut data, 0, 9, 4, &mut lt); // pivot value=5
 
         // Everything before pivot_pos should be <= 5
         for i in 0..pivot_pos {
@@ -107,7 +910,7 @@ mod tests {
     #[test]
     fn test_partition_reverse_sorted() {
         let mut data = vec![10, 9, 8, 7, 6, 5, 4, 3, 2, 1];
-        let pivot_pos = QuickSelect::partition(&mut data, 0, 9, 4); // pivot value=6
+        let (pivot_pos, _) = QuickSelect::partition(&mut data, 0, 9, 4, &mut lt); // pivot value=6
 
         for i in 0..pivot_pos {
             assert!(data[i] <= data[pivot_pos]);
@@ -120,16 +923,16 @@ mod tests {
     #[test]
     fn test_partition_all_equal() {
         let mut data = vec![5, 5, 5, 5, 5];
-        let pivot_pos = QuickSelect::partition(&mut data, 0, 4, 2);
-        // Should not infinite loop, pivot goes somewhere valid
-        assert!(pivot_pos <= 4);
+        let (lo, hi) = QuickSelect::partition(&mut data, 0, 4, 2, &mut lt);
+        // Should not infinite loop, the whole range is the pivot's run
+        assert_eq!((lo, hi), (0, 4));
     }
 
     #[test]
     fn test_partition_subarray() {
         let mut data = vec![100, 5, 3, 8, 2, 7, 100];
         // Only partition indices 1..5, leave 0 and 6 untouched
-        let pivot_pos = QuickSelect::partition(&mut data, 1, 5, 3); // pivot value=8
+        let (pivot_pos, _) = QuickSelect::partition(&mut data, 1, 5, 3, &mut lt); // pivot value=8
 
         assert_eq!(data[0], 100); // Untouched
         assert_eq!(data[6], 100); // Untouched
@@ -145,11 +948,338 @@ mod tests {
     #[test]
     fn test_select_1() {
         let mut data = vec![7, 2, 5, 1, 8, 3];
-        let right = data.len() - 1;
-        // left = 0
-        // right = 5
         // 3-rd smallest is "index = 2"
-        let elem = QuickSelect::select(data.as_mut_slice(), 0, 5, 2);
+        let elem = *select_nth(data.as_mut_slice(), 2);
         assert_eq!(elem, 3);
     }
+
+    /// `n` keys below 1000, with plenty of duplicates, from a seeded generator.
+    fn random_keys(seed: u64, n: usize) -> Vec<u64> {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        (0..n).map(|_| rng.random_range(0..1_000)).collect()
+    }
+
+    fn assert_partitioned<T: Ord>(data: &[T], k: usize) {
+        assert!(data[..k].iter().all(|x| *x <= data[k]));
+        assert!(data[k + 1..].iter().all(|x| *x >= data[k]));
+    }
+
+    #[test]
+    fn test_select_nth_matches_sort() {
+        let data = random_keys(0x9e37_79b9_7f4a_7c15, 500);
+        let mut sorted = data.clone();
+        sorted.sort_unstable();
+
+        for (k, expected) in sorted.iter().enumerate() {
+            let mut work = data.clone();
+            assert_eq!(select_nth(&mut work, k), expected);
+            assert_partitioned(&work, k);
+        }
+    }
+
+    #[test]
+    fn test_select_nth_by_and_key() {
+        let mut data = vec![(3, 'c'), (1, 'a'), (4, 'd'), (2, 'b')];
+        assert_eq!(*select_nth_by_key(&mut data, 0, |p| p.1), (1, 'a'));
+        assert_eq!(*select_nth_by(&mut data, 0, |a, b| b.cmp(a)), (4, 'd'));
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn test_select_nth_out_of_range() {
+        select_nth(&mut [1, 2, 3], 3);
+    }
+
+    #[test]
+    fn test_top_k_and_median() {
+        let mut data = random_keys(42, 101);
+        let mut sorted = data.clone();
+        sorted.sort_unstable();
+
+        let mut top = top_k(&mut data, 10).to_vec();
+        top.sort_unstable();
+        assert_eq!(top, sorted[91..]);
+        assert!(top_k(&mut data, 0).is_empty());
+        assert_eq!(top_k(&mut data, 101).len(), 101);
+
+        assert_eq!(median(&mut data), Some(&sorted[50]));
+        assert_eq!(median(&mut [4, 1, 3, 2]), Some(&2));
+        assert_eq!(median::<u32>(&mut []), None);
+    }
+
+    #[test]
+    fn test_partition_three_way() {
+        let mut data = vec![3, 1, 3, 4, 3, 0, 5, 3];
+        let (lo, hi) = QuickSelect::partition(&mut data, 0, 7, 0, &mut lt);
+        assert_eq!((lo, hi), (2, 5));
+        assert!(data[..lo].iter().all(|&x| x < 3));
+        assert!(data[lo..=hi].iter().all(|&x| x == 3));
+        assert!(data[hi + 1..].iter().all(|&x| x > 3));
+    }
+
+    #[test]
+    fn test_duplicate_heavy_is_linear() {
+        let mut comparisons = 0;
+        let mut data = vec![7u32; 100_000];
+        data.extend(0..10);
+        let n = data.len();
+        select_nth_by(&mut data, n / 2, |a, b| {
+            comparisons += 1;
+            a.cmp(b)
+        });
+        assert_eq!(data[n / 2], 7);
+        assert!(comparisons < 4 * n, "{comparisons} comparisons");
+    }
+
+    #[test]
+    fn test_median_of_medians_pivot() {
+        let data = random_keys(7, 1_000);
+        for (left, right) in [(0, 999), (10, 14), (100, 600)] {
+            let mut work = data.clone();
+            let pivot = QuickSelect::median_of_medians(&mut work, left, right, &mut u64::lt);
+            let (lo, hi) = QuickSelect::partition(&mut work, left, right, pivot, &mut u64::lt);
+            let n = right - left + 1;
+            assert!(lo - left <= 7 * n / 10 && right - hi <= 7 * n / 10);
+        }
+    }
+
+    #[test]
+    fn test_introselect_fallback_matches_sort() {
+        // A zero budget forces a median-of-medians pivot on every round.
+        let data = random_keys(11, 777);
+        let mut sorted = data.clone();
+        sorted.sort_unstable();
+
+        for k in (0..data.len()).step_by(37) {
+            let mut work = data.clone();
+            QuickSelect::introselect(&mut work, 0, data.len() - 1, k, 0, false, &mut u64::lt);
+            assert_eq!(work[k], sorted[k]);
+            assert_partitioned(&work, k);
+        }
+    }
+
+    #[test]
+    fn test_introselect_fallback_is_linear() {
+        for n in [10_000, 100_000] {
+            let mut data = random_keys(13, n);
+            let mut comparisons = 0;
+            QuickSelect::introselect(&mut data, 0, n - 1, n / 2, 0, false, &mut |a, b| {
+                comparisons += 1;
+                a < b
+            });
+            assert!(
+                comparisons < 16 * n,
+                "{comparisons} comparisons for {n} keys"
+            );
+        }
+    }
+
+    #[test]
+    fn test_floyd_rivest_matches_sort() {
+        for (seed, n) in [(1, 10), (2, 601), (3, 5_000), (4, 20_000)] {
+            let data = random_keys(seed, n);
+            let mut sorted = data.clone();
+            sorted.sort_unstable();
+
+            for k in [0, 1, n / 3, n / 2, n - 2, n - 1] {
+                let mut work = data.clone();
+                assert_eq!(
+                    select_nth_with(&mut work, k, Strategy::FloydRivest),
+                    &sorted[k]
+                );
+                assert_partitioned(&work, k);
+            }
+        }
+    }
+
+    #[test]
+    fn test_floyd_rivest_sorted_and_equal() {
+        let n = 10_000;
+        for data in [
+            (0..n).collect::<Vec<u32>>(),
+            (0..n).rev().collect(),
+            vec![3; n as usize],
+        ] {
+            let mut sorted = data.clone();
+            sorted.sort_unstable();
+            for k in [0, 17, 5_000, 9_999] {
+                let mut work = data.clone();
+                assert_eq!(
+                    select_nth_with(&mut work, k, Strategy::FloydRivest),
+                    &sorted[k]
+                );
+                assert_partitioned(&work, k);
+            }
+        }
+    }
+
+    #[test]
+    fn test_select_many() {
+        let data = random_keys(5, 10_000);
+        let mut sorted = data.clone();
+        sorted.sort_unstable();
+
+        let ks = [9_990, 5_000, 0, 9_000, 5_000, 9_999, 1];
+        let mut work = data.clone();
+        let selected: Vec<u64> = select_many(&mut work, &ks).into_iter().copied().collect();
+        let expected: Vec<u64> = ks.iter().map(|&k| sorted[k]).collect();
+        assert_eq!(selected, expected);
+        for k in ks {
+            assert_partitioned(&work, k);
+        }
+
+        assert!(select_many(&mut work, &[]).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn test_select_many_out_of_range() {
+        select_many(&mut [1, 2, 3], &[0, 3]);
+    }
+
+    #[test]
+    fn test_parallel_select_matches_sort() {
+        let data = random_keys(13, 50_000);
+        let mut sorted = data.clone();
+        sorted.sort_unstable();
+
+        for threads in [1, 2, 3, 8] {
+            for k in [0, 1, 12_345, 25_000, 49_999] {
+                let mut work = data.clone();
+                ParallelSelect::select(&mut work, k, threads, 1_000);
+                assert_eq!(work[k], sorted[k]);
+                assert_partitioned(&work, k);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parallel_select_duplicates() {
+        let mut data: Vec<u32> = (0..100_000).map(|i| i % 3).collect();
+        ParallelSelect::select(&mut data, 70_000, 4, 1_000);
+        assert_eq!(data[70_000], 2);
+        assert_partitioned(&data, 70_000);
+
+        let mut data = random_keys(17, 200_000);
+        let mut sorted = data.clone();
+        sorted.sort_unstable();
+        assert_eq!(par_select_nth(&mut data, 150_000), &sorted[150_000]);
+    }
+
+    #[test]
+    fn test_partition_in_blocks() {
+        for n in [0, 1, 2, 127, 128, 129, 255, 256, 257, 300, 1_000, 4_099] {
+            for (seed, modulo) in [(19, 1_000), (23, 2), (29, 1)] {
+                let data: Vec<u64> = random_keys(seed, n)
+                    .into_iter()
+                    .map(|x| x % modulo)
+                    .collect();
+                let split = modulo / 2;
+                let mut work = data.clone();
+                let mid = partition_in_blocks(&mut work, &mut |&x| x < split);
+
+                assert_eq!(mid, data.iter().filter(|&&x| x < split).count());
+                assert!(work[..mid].iter().all(|&x| x < split));
+                assert!(work[mid..].iter().all(|&x| x >= split));
+                let (mut a, mut b) = (data, work);
+                a.sort_unstable();
+                b.sort_unstable();
+                assert_eq!(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_public_partitions() {
+        let data = random_keys(31, 1_000);
+        let mut work = data.clone();
+        let mid = bench::partition(&mut work, 500);
+        assert_eq!(work[mid], data[500]);
+        assert!(work[..mid].iter().all(|&x| x < work[mid]));
+        assert!(work[mid..].iter().all(|&x| x >= work[mid]));
+
+        let mut work = data.clone();
+        let (lo, hi) = bench::partition_three_way(&mut work, 500);
+        assert!(work[..lo].iter().all(|&x| x < data[500]));
+        assert!(work[lo..=hi].iter().all(|&x| x == data[500]));
+        assert!(work[hi + 1..].iter().all(|&x| x > data[500]));
+    }
+
+    #[test]
+    fn test_select_sorted_and_few_distinct() {
+        let n = 20_000u32;
+        for data in [
+            (0..n).collect::<Vec<_>>(),
+            (0..n).rev().collect(),
+            (0..n).map(|i| i % 4).collect(),
+        ] {
+            let mut sorted = data.clone();
+            sorted.sort_unstable();
+            for k in [0, 1, 9_999, 19_999] {
+                let mut work = data.clone();
+                assert_eq!(select_nth(&mut work, k), &sorted[k]);
+                assert_partitioned(&work, k);
+            }
+        }
+    }
+
+    #[test]
+    fn test_select_non_copy_keys() {
+        let data: Vec<String> = random_keys(37, 3_000)
+            .iter()
+            .map(|x| format!("{x:04}"))
+            .collect();
+        let mut sorted = data.clone();
+        sorted.sort_unstable();
+        for k in [0, 1_500, 2_999] {
+            let mut work = data.clone();
+            assert_eq!(select_nth(&mut work, k), &sorted[k]);
+        }
+    }
+
+    #[test]
+    fn test_top_k_heap_and_select_paths() {
+        let data = random_keys(41, 10_000);
+        let mut sorted = data.clone();
+        sorted.sort_unstable();
+
+        // k = 10 takes the heap, k = 1_000 quickselect.
+        for k in [1, 10, 100, 1_000, 9_999] {
+            let mut work = data.clone();
+            let mut top = top_k(&mut work, k).to_vec();
+            top.sort_unstable();
+            assert_eq!(top, sorted[10_000 - k..]);
+            assert_partitioned(&work, 10_000 - k);
+        }
+    }
+
+    #[test]
+    fn test_top_k_accumulator() {
+        let data = random_keys(43, 5_000);
+        let mut sorted = data.clone();
+        sorted.sort_unstable();
+
+        let mut whole = TopK::new(50);
+        whole.extend(data.iter().copied());
+        assert_eq!(whole.len(), 50);
+        assert_eq!(whole.peek_min(), Some(&sorted[4_950]));
+
+        let mut merged = TopK::new(50);
+        for chunk in data.chunks(700) {
+            let mut part = TopK::new(50);
+            part.extend(chunk.iter().copied());
+            merged.merge(part);
+        }
+
+        assert_eq!(whole.into_sorted_vec(), sorted[4_950..]);
+        assert_eq!(merged.into_sorted_vec(), sorted[4_950..]);
+
+        let mut none = TopK::new(0);
+        none.push(1);
+        assert!(none.is_empty());
+
+        let mut few = TopK::new(10);
+        few.extend([3, 1, 2]);
+        assert_eq!(few.into_sorted_vec(), vec![1, 2, 3]);
+    }
 }
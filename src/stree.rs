@@ -0,0 +1,338 @@
+//! Static B+-tree (S+-tree) with SIMD node search.
+//!
+//! Follows the [static B-tree layout](https://en.algorithmica.org/hpc/data-structures/s-tree/)
+//! that continues the Eytzinger article: keys are grouped into nodes of 16,
+//! each node is aligned to a cache line, and the rank of a key inside a node
+//! is found with a handful of vector comparisons and a popcount instead of a
+//! chain of dependent branches.
+//!
+//! ```text
+//!  layer 2 (root)          [ s s s ... ]
+//!                          /  |   \
+//!  layer 1           [ s s ... ] [ s s ... ] ...        17 children per node
+//!                     /   |
+//!  layer 0 (leaves) [ k k ... k ][ k k ... k ] ...      sorted keys, 16 per node
+//! ```
+//!
+//! The leaves hold the sorted keys contiguously, padded with `MAX`, so the
+//! position a search lands on in the leaf layer is directly the key's rank.
+//! Separator `i` of an internal node is the smallest key in the subtree of
+//! child `i + 1`.
+
+/// Keys per node.
+const B: usize = 16;
+
+/// Number of keys [`STree::lower_bound_batch`] keeps in flight.
+const BATCH: usize = 16;
+
+#[repr(C, align(64))]
+#[derive(Clone, Copy)]
+struct Node<T> {
+    keys: [T; B],
+}
+
+/// Key types that an [`STree`] can index.
+pub trait STreeKey: Copy + Ord {
+    /// Padding value for unused slots.
+    const MAX: Self;
+
+    /// Returns the number of keys in `keys` that are less than `x`.
+    fn rank(keys: &[Self; B], x: Self) -> usize;
+}
+
+impl STreeKey for i32 {
+    const MAX: Self = i32::MAX;
+
+    #[inline(always)]
+    fn rank(keys: &[Self; B], x: Self) -> usize {
+        #[cfg(target_arch = "x86_64")]
+        {
+            // SAFETY: SSE2 is part of the x86_64 baseline and `keys` is 64 bytes.
+            unsafe { sse2::rank32(keys.as_ptr() as *const u8, x, 0) }
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        {
+            scalar_rank(keys, x)
+        }
+    }
+}
+
+impl STreeKey for u32 {
+    const MAX: Self = u32::MAX;
+
+    #[inline(always)]
+    fn rank(keys: &[Self; B], x: Self) -> usize {
+        #[cfg(target_arch = "x86_64")]
+        {
+            // Flipping the sign bit maps unsigned order onto signed order.
+            // SAFETY: SSE2 is part of the x86_64 baseline and `keys` is 64 bytes.
+            unsafe { sse2::rank32(keys.as_ptr() as *const u8, (x ^ (1 << 31)) as i32, 1 << 31) }
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        {
+            scalar_rank(keys, x)
+        }
+    }
+}
+
+impl STreeKey for u64 {
+    const MAX: Self = u64::MAX;
+
+    #[inline(always)]
+    fn rank(keys: &[Self; B], x: Self) -> usize {
+        // SSE2 has no 64-bit compare; this loop is simple enough for the
+        // autovectorizer to pick whatever the target offers.
+        scalar_rank(keys, x)
+    }
+}
+
+#[inline(always)]
+#[allow(dead_code)]
+fn scalar_rank<T: Ord + Copy>(keys: &[T; B], x: T) -> usize {
+    keys.iter().map(|&k| (k < x) as usize).sum()
+}
+
+#[cfg(target_arch = "x86_64")]
+mod sse2 {
+    use std::arch::x86_64::*;
+
+    /// Counts the 32-bit lanes of the 64-byte block at `ptr` that compare less
+    /// than `x` as signed integers once XORed with `flip`.
+    ///
+    /// The loads are unaligned so any `[_; 16]` is accepted, but tree nodes are
+    /// cache-line aligned so they never straddle two lines.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid for reads of 64 bytes.
+    #[inline(always)]
+    pub(super) unsafe fn rank32(ptr: *const u8, x: i32, flip: i32) -> usize {
+        unsafe {
+            let p = ptr as *const __m128i;
+            let x = _mm_set1_epi32(x);
+            let flip = _mm_set1_epi32(flip);
+
+            let lt = |v: __m128i| _mm_cmpgt_epi32(x, _mm_xor_si128(v, flip));
+            let m0 = lt(_mm_loadu_si128(p));
+            let m1 = lt(_mm_loadu_si128(p.add(1)));
+            let m2 = lt(_mm_loadu_si128(p.add(2)));
+            let m3 = lt(_mm_loadu_si128(p.add(3)));
+
+            // Narrow the four 32-bit masks to one byte per lane.
+            let m = _mm_packs_epi16(_mm_packs_epi32(m0, m1), _mm_packs_epi32(m2, m3));
+            (_mm_movemask_epi8(m) as u32).count_ones() as usize
+        }
+    }
+}
+
+/// Static search tree over sorted keys, see the [module docs](self).
+pub struct STree<T> {
+    /// All layers, leaves first.
+    nodes: Vec<Node<T>>,
+    /// Index in `nodes` of the first node of each layer, leaves first.
+    offsets: Vec<usize>,
+    len: usize,
+}
+
+impl<T: STreeKey> STree<T> {
+    /// Builds the tree from a sorted slice.
+    pub fn new(sorted: &[T]) -> Self {
+        debug_assert!(sorted.is_sorted(), "input must be sorted");
+        let n = sorted.len();
+
+        let mut sizes = vec![n.div_ceil(B)];
+        while *sizes.last().unwrap() > 1 {
+            sizes.push(sizes.last().unwrap().div_ceil(B + 1));
+        }
+
+        let mut offsets = Vec::with_capacity(sizes.len());
+        let mut total = 0;
+        for &size in &sizes {
+            offsets.push(total);
+            total += size;
+        }
+
+        let mut nodes = vec![Node { keys: [T::MAX; B] }; total];
+
+        for (i, &key) in sorted.iter().enumerate() {
+            nodes[i / B].keys[i % B] = key;
+        }
+
+        // Node `j` of layer `l` spans leaves starting at `j * (B + 1)^(l - 1) * B`
+        // keys in; each separator is the first key of the matching child.
+        let mut span = 1;
+        for l in 1..sizes.len() {
+            for j in 0..sizes[l] {
+                let node = &mut nodes[offsets[l] + j];
+                for i in 0..B {
+                    let child = j * (B + 1) + i + 1;
+                    let first = child * span * B;
+                    if child < sizes[l - 1] && first < n {
+                        node.keys[i] = sorted[first];
+                    }
+                }
+            }
+            span *= B + 1;
+        }
+
+        Self {
+            nodes,
+            offsets,
+            len: n,
+        }
+    }
+
+    /// Returns the rank of the first key that is not less than `x`, or `len`
+    /// if every key is less than `x`.
+    pub fn lower_bound(&self, x: T) -> usize {
+        if self.len == 0 {
+            return 0;
+        }
+
+        let mut j = 0;
+        for &offset in self.offsets[1..].iter().rev() {
+            j = j * (B + 1) + T::rank(&self.nodes[offset + j].keys, x);
+        }
+
+        (j * B + T::rank(&self.nodes[j].keys, x)).min(self.len)
+    }
+
+    /// Returns the rank of `x`, or `None` if it is not present. With duplicates
+    /// the rank of the first occurrence is returned.
+    pub fn search(&self, x: T) -> Option<usize> {
+        let rank = self.lower_bound(x);
+        if rank < self.len && self.nodes[rank / B].keys[rank % B] == x {
+            Some(rank)
+        } else {
+            None
+        }
+    }
+
+    /// Computes [`lower_bound`](Self::lower_bound) for every key in `xs`,
+    /// writing the results into `out`.
+    ///
+    /// Keys descend in groups of sixteen, one layer per round, so the node
+    /// loads of a round are independent and their cache misses overlap.
+    ///
+    /// Panics if `xs` and `out` differ in length.
+    pub fn lower_bound_batch(&self, xs: &[T], out: &mut [usize]) {
+        assert_eq!(xs.len(), out.len(), "xs and out must have equal length");
+        if self.len == 0 {
+            out.fill(0);
+            return;
+        }
+
+        for (xs, out) in xs.chunks(BATCH).zip(out.chunks_mut(BATCH)) {
+            let mut j = [0usize; BATCH];
+            let j = &mut j[..xs.len()];
+
+            for &offset in self.offsets[1..].iter().rev() {
+                for (j, &x) in j.iter_mut().zip(xs) {
+                    *j = *j * (B + 1) + T::rank(&self.nodes[offset + *j].keys, x);
+                }
+            }
+
+            for ((&j, &x), out) in j.iter().zip(xs).zip(out.iter_mut()) {
+                *out = (j * B + T::rank(&self.nodes[j].keys, x)).min(self.len);
+            }
+        }
+    }
+
+    /// Returns the number of keys in the tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the tree holds no keys.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_lower_bound<T: STreeKey + std::fmt::Debug>(sorted: &[T], queries: &[T]) {
+        let tree = STree::new(sorted);
+        let mut batch = vec![usize::MAX; queries.len()];
+        tree.lower_bound_batch(queries, &mut batch);
+
+        for (&x, &batched) in queries.iter().zip(&batch) {
+            let expected = sorted.partition_point(|&k| k < x);
+            assert_eq!(
+                tree.lower_bound(x),
+                expected,
+                "n = {}, x = {x:?}",
+                sorted.len()
+            );
+            assert_eq!(batched, expected, "n = {}, x = {x:?}", sorted.len());
+        }
+    }
+
+    #[test]
+    fn node_is_cache_line_aligned() {
+        assert_eq!(std::mem::align_of::<Node<u32>>(), 64);
+        assert_eq!(std::mem::size_of::<Node<u32>>(), 64);
+        assert_eq!(std::mem::size_of::<Node<u64>>(), 128);
+    }
+
+    #[test]
+    fn rank_matches_scalar() {
+        let node = Node {
+            keys: std::array::from_fn(|i| i as i32 * 3 - 20),
+        };
+        let unode = Node {
+            keys: std::array::from_fn(|i| (i as u32) << 28 | 7),
+        };
+        for x in -30..30 {
+            assert_eq!(i32::rank(&node.keys, x), scalar_rank(&node.keys, x));
+        }
+        for x in [0, 6, 7, 8, 1 << 31, (1 << 31) + 7, u32::MAX] {
+            assert_eq!(u32::rank(&unode.keys, x), scalar_rank(&unode.keys, x));
+        }
+    }
+
+    #[test]
+    fn lower_bound_u32() {
+        // Cover one, two and three layers along with partially filled nodes.
+        for n in [0u32, 1, 15, 16, 17, 100, 272, 273, 300, 5000] {
+            let sorted: Vec<u32> = (0..n).map(|x| x * 2 + 1).collect();
+            let queries: Vec<u32> = (0..2 * n + 3).chain([u32::MAX]).collect();
+            check_lower_bound(&sorted, &queries);
+        }
+    }
+
+    #[test]
+    fn lower_bound_i32_duplicates() {
+        for n in [1i32, 16, 50, 400, 3000] {
+            let sorted: Vec<i32> = (0..n).map(|x| x / 5 - n / 10).collect();
+            let queries: Vec<i32> = (-n..n).chain([i32::MIN, i32::MAX]).collect();
+            check_lower_bound(&sorted, &queries);
+        }
+    }
+
+    #[test]
+    fn lower_bound_u64_with_max_keys() {
+        let sorted: Vec<u64> = (0..40)
+            .map(|x| x * 10)
+            .chain([u64::MAX, u64::MAX])
+            .collect();
+        let queries: Vec<u64> = (0..420).chain([u64::MAX - 1, u64::MAX]).collect();
+        check_lower_bound(&sorted, &queries);
+    }
+
+    #[test]
+    fn search() {
+        let sorted: Vec<u32> = (0..1000).map(|x| x * 3).collect();
+        let tree = STree::new(&sorted);
+        for x in 0..3000 {
+            let expected = if x % 3 == 0 {
+                Some(x as usize / 3)
+            } else {
+                None
+            };
+            assert_eq!(tree.search(x), expected);
+        }
+    }
+}